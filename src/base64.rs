@@ -0,0 +1,111 @@
+// Standard base64 (RFC 4648, with padding) for inline media sources
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes as padded standard base64
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decode padded or unpadded standard base64, ignoring ASCII whitespace
+///
+/// Padding, if present, must be complete and end the input.
+pub(crate) fn decode(encoded: &str) -> Result<Vec<u8>, String> {
+    let mut digits = Vec::with_capacity(encoded.len());
+    let mut padding = 0;
+    for (position, byte) in encoded.bytes().enumerate() {
+        if byte.is_ascii_whitespace() {
+            continue;
+        }
+        if byte == b'=' {
+            padding += 1;
+            continue;
+        }
+        if padding > 0 {
+            return Err(format!(
+                "unexpected base64 data after padding at offset {}",
+                position
+            ));
+        }
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b => {
+                return Err(format!(
+                    "invalid base64 character {:?} at offset {}",
+                    b as char, position
+                ))
+            }
+        };
+        digits.push(value);
+    }
+    if digits.len() % 4 == 1 {
+        return Err("invalid base64 length".to_string());
+    }
+    if padding > 0 && (padding > 2 || (digits.len() + padding) % 4 != 0) {
+        return Err("invalid base64 padding".to_string());
+    }
+
+    let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+    for chunk in digits.chunks(4) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &digit)| n | u32::from(digit) << (18 - 6 * i));
+        bytes.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_known_vectors() {
+        let cases = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (plain, encoded) in cases {
+            assert_eq!(encode(plain.as_bytes()), encoded);
+            assert_eq!(decode(encoded).unwrap(), plain.as_bytes());
+        }
+        assert_eq!(decode("Zm9v\nYmFy").unwrap(), b"foobar");
+        assert_eq!(decode("Zm8").unwrap(), b"fo");
+        assert!(decode("Zm9v!").is_err());
+        assert!(decode("Z").is_err());
+
+        // Only whitespace may follow padding, and padding must complete the last quantum
+        assert_eq!(decode("Zm8=\n").unwrap(), b"fo");
+        assert!(decode("Zm9v=!!!garbage").is_err());
+        assert!(decode("Zg==Zm8=").is_err());
+        assert!(decode("Zm9v=").is_err());
+        assert!(decode("Zg=").is_err());
+        assert!(decode("Zg===").is_err());
+    }
+}
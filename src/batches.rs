@@ -0,0 +1,204 @@
+use crate::errors::{AnthropicError, ApiErrorBody};
+use crate::messages::{CompletionRequest, CompletionResponse};
+use serde::{Deserialize, Serialize};
+
+/// A single request within a message batch
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BatchRequest {
+    /// Caller-provided ID used to match results to requests
+    pub custom_id: String,
+
+    /// Parameters of the completion request
+    pub params: CompletionRequest,
+}
+
+/// Request to create a new message batch
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CreateBatchRequest {
+    /// Requests to process in the batch
+    pub requests: Vec<BatchRequest>,
+}
+
+/// Processing status of a message batch
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingStatus {
+    InProgress,
+    Canceling,
+    Ended,
+}
+
+/// Number of requests in a batch in each state
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct RequestCounts {
+    pub processing: u32,
+    pub succeeded: u32,
+    pub errored: u32,
+    pub canceled: u32,
+    pub expired: u32,
+}
+
+/// A message batch as returned by the Batches API
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MessageBatch {
+    /// ID of the batch
+    pub id: String,
+
+    /// Object type, always "message_batch"
+    #[serde(rename = "type")]
+    pub batch_type: String,
+
+    /// Processing status of the batch
+    pub processing_status: ProcessingStatus,
+
+    /// Number of requests in each state
+    pub request_counts: RequestCounts,
+
+    /// RFC 3339 time at which processing ended
+    pub ended_at: Option<String>,
+
+    /// RFC 3339 time at which the batch was created
+    pub created_at: String,
+
+    /// RFC 3339 time at which the batch will expire
+    pub expires_at: String,
+
+    /// RFC 3339 time at which the batch was archived
+    pub archived_at: Option<String>,
+
+    /// RFC 3339 time at which cancellation was initiated
+    pub cancel_initiated_at: Option<String>,
+
+    /// URL of the results file, available once processing has ended
+    pub results_url: Option<String>,
+}
+
+/// A single line of a batch results file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BatchResultLine {
+    /// Caller-provided ID of the request this result belongs to
+    pub custom_id: String,
+
+    /// Outcome of the request
+    pub result: BatchResult,
+}
+
+/// Outcome of a single batch request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum BatchResult {
+    /// The request completed successfully
+    #[serde(rename = "succeeded")]
+    Succeeded { message: CompletionResponse },
+
+    /// The request failed
+    #[serde(rename = "errored")]
+    Errored { error: ApiErrorBody },
+
+    /// The batch was canceled before the request was processed
+    #[serde(rename = "canceled")]
+    Canceled,
+
+    /// The batch expired before the request was processed
+    #[serde(rename = "expired")]
+    Expired,
+}
+
+impl BatchResultLine {
+    /// Parse the contents of a JSONL results file, skipping blank lines
+    pub fn parse_jsonl(jsonl: &str) -> Result<Vec<BatchResultLine>, AnthropicError> {
+        jsonl
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(AnthropicError::from))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_create_batch_request() {
+        let json = serde_json::json!({
+            "requests": [
+                {
+                    "custom_id": "my-first-request",
+                    "params": {
+                        "model": "claude-3-7-sonnet-20250219",
+                        "max_tokens": 1024,
+                        "messages": [{"role": "user", "content": "Hello, world"}]
+                    }
+                },
+                {
+                    "custom_id": "my-second-request",
+                    "params": {
+                        "model": "claude-3-7-sonnet-20250219",
+                        "max_tokens": 1024,
+                        "messages": [{"role": "user", "content": "Hi again, friend"}]
+                    }
+                }
+            ]
+        });
+
+        let request: CreateBatchRequest = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(request.requests[1].custom_id, "my-second-request");
+        assert_eq!(serde_json::to_value(&request).unwrap(), json);
+    }
+
+    #[test]
+    fn test_round_trip_message_batch() {
+        let json = serde_json::json!({
+            "id": "msgbatch_013Zva2CMHLNnXjNJJKqJ2EF",
+            "type": "message_batch",
+            "processing_status": "ended",
+            "request_counts": {
+                "processing": 0,
+                "succeeded": 95,
+                "errored": 3,
+                "canceled": 1,
+                "expired": 1
+            },
+            "ended_at": "2024-08-20T18:37:24.100435Z",
+            "created_at": "2024-08-20T18:37:24.100435Z",
+            "expires_at": "2024-08-21T18:37:24.100435Z",
+            "archived_at": null,
+            "cancel_initiated_at": null,
+            "results_url": "https://api.anthropic.com/v1/messages/batches/msgbatch_013Zva2CMHLNnXjNJJKqJ2EF/results"
+        });
+
+        let batch: MessageBatch = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(batch.processing_status, ProcessingStatus::Ended);
+        assert_eq!(batch.request_counts.succeeded, 95);
+        assert!(batch.results_url.is_some());
+        assert_eq!(serde_json::to_value(&batch).unwrap(), json);
+    }
+
+    #[test]
+    fn test_parse_results_jsonl() {
+        let jsonl = r#"{"custom_id":"my-first-request","result":{"type":"succeeded","message":{"id":"msg_014VwiXbi91y3JMjcpyGBHX5","type":"message","role":"assistant","model":"claude-3-7-sonnet-20250219","content":[{"type":"text","text":"Hello again! It's nice to see you."}],"stop_reason":"end_turn","stop_sequence":null,"usage":{"input_tokens":11,"output_tokens":36}}}}
+
+{"custom_id":"my-second-request","result":{"type":"errored","error":{"type":"error","error":{"type":"invalid_request_error","message":"Validation error: max_tokens must be positive"}}}}
+{"custom_id":"my-third-request","result":{"type":"expired"}}
+"#;
+
+        let lines = BatchResultLine::parse_jsonl(jsonl).expect("Failed to parse results");
+        assert_eq!(lines.len(), 3);
+        assert!(matches!(lines[0].result, BatchResult::Succeeded { .. }));
+        match &lines[1].result {
+            BatchResult::Errored { error } => {
+                assert_eq!(error.error.error_type, "invalid_request_error")
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert_eq!(lines[2].custom_id, "my-third-request");
+        assert!(matches!(lines[2].result, BatchResult::Expired));
+    }
+
+    #[test]
+    fn test_parse_results_jsonl_invalid_line() {
+        let result = BatchResultLine::parse_jsonl("{\"custom_id\": \"a\"}\n");
+        assert!(matches!(result, Err(AnthropicError::JsonError(_))));
+    }
+}
@@ -1,14 +1,19 @@
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fmt;
+use std::time::Duration;
 
 /// Error type for Anthropic API operations
 #[derive(Debug)]
 pub enum AnthropicError {
     /// HTTP request failed
-    HttpError(String),
+    HttpError {
+        message: String,
+        source: Option<Box<dyn Error + Send + Sync>>,
+    },
 
     /// Failed to serialize/deserialize JSON
-    JsonError(String),
+    JsonError(Box<serde_json::Error>),
 
     /// API returned an error
     ApiError { status: u16, message: String },
@@ -21,12 +26,125 @@ pub enum AnthropicError {
 
     /// Authentication error
     AuthenticationError(String),
+
+    /// The API is temporarily overloaded (HTTP 529)
+    Overloaded,
+}
+
+/// The standard error envelope returned by the Anthropic API
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApiErrorBody {
+    /// Envelope type, always "error"
+    #[serde(rename = "type")]
+    pub body_type: String,
+
+    /// Details of the error
+    pub error: ApiErrorDetail,
+}
+
+/// Details of an API error
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ApiErrorDetail {
+    /// Error type, e.g. "invalid_request_error" or "overloaded_error"
+    #[serde(rename = "type")]
+    pub error_type: String,
+
+    /// Human-readable error message
+    pub message: String,
+}
+
+impl AnthropicError {
+    /// Create an HTTP error from a message
+    pub fn http(message: impl Into<String>) -> Self {
+        AnthropicError::HttpError {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Create an HTTP error wrapping the underlying transport error
+    pub fn http_with_source(source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        let source = source.into();
+        AnthropicError::HttpError {
+            message: source.to_string(),
+            source: Some(source),
+        }
+    }
+
+    /// Classify an HTTP error response by its status code
+    ///
+    /// The message is taken from the standard Anthropic error envelope when the
+    /// body contains one, and is the raw body otherwise.
+    pub fn from_status(status: u16, body: &str) -> Self {
+        let message = serde_json::from_str::<ApiErrorBody>(body)
+            .map(|envelope| envelope.error.message)
+            .unwrap_or_else(|_| body.to_string());
+
+        match status {
+            401 | 403 => AnthropicError::AuthenticationError(message),
+            429 => AnthropicError::RateLimitExceeded { retry_after: None },
+            529 => AnthropicError::Overloaded,
+            _ => AnthropicError::ApiError { status, message },
+        }
+    }
+
+    /// Classify an HTTP error response by the `error.type` in its envelope
+    ///
+    /// Falls back to `from_status` when the body is not a standard error envelope.
+    pub fn from_response_body(status: u16, body: &str) -> Self {
+        match serde_json::from_str::<ApiErrorBody>(body) {
+            Ok(envelope) => {
+                Self::from_error_type(status, &envelope.error.error_type, envelope.error.message)
+            }
+            Err(_) => Self::from_status(status, body),
+        }
+    }
+
+    /// Whether the failed request may succeed if retried later
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AnthropicError::RateLimitExceeded { .. } | AnthropicError::Overloaded => true,
+            AnthropicError::ApiError { status, .. } => (500..600).contains(status),
+            _ => false,
+        }
+    }
+
+    /// How long to wait before retrying, if the API provided a hint
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            AnthropicError::RateLimitExceeded {
+                retry_after: Some(seconds),
+            } => Some(Duration::from_secs(*seconds)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn from_error_type(status: u16, error_type: &str, message: String) -> Self {
+        match error_type {
+            "authentication_error" => AnthropicError::AuthenticationError(message),
+            "rate_limit_error" => AnthropicError::RateLimitExceeded { retry_after: None },
+            "overloaded_error" => AnthropicError::Overloaded,
+            _ => AnthropicError::ApiError { status, message },
+        }
+    }
+
+    /// Attach a retry hint (in seconds) to a rate limit error
+    ///
+    /// Other variants are returned unchanged.
+    pub fn with_retry_after(self, seconds: u64) -> Self {
+        match self {
+            AnthropicError::RateLimitExceeded { .. } => AnthropicError::RateLimitExceeded {
+                retry_after: Some(seconds),
+            },
+            other => other,
+        }
+    }
 }
 
 impl fmt::Display for AnthropicError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            AnthropicError::HttpError(msg) => write!(f, "HTTP error: {}", msg),
+            AnthropicError::HttpError { message, .. } => write!(f, "HTTP error: {}", message),
             AnthropicError::JsonError(msg) => write!(f, "JSON error: {}", msg),
             AnthropicError::ApiError { status, message } => {
                 write!(f, "API error ({}): {}", status, message)
@@ -40,14 +158,171 @@ impl fmt::Display for AnthropicError {
                 }
             }
             AnthropicError::AuthenticationError(msg) => write!(f, "Authentication error: {}", msg),
+            AnthropicError::Overloaded => write!(f, "API is overloaded"),
         }
     }
 }
 
-impl Error for AnthropicError {}
+impl Error for AnthropicError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AnthropicError::HttpError {
+                source: Some(source),
+                ..
+            } => Some(source.as_ref()),
+            AnthropicError::JsonError(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 impl From<serde_json::Error> for AnthropicError {
     fn from(error: serde_json::Error) -> Self {
-        AnthropicError::JsonError(error.to_string())
+        AnthropicError::JsonError(Box::new(error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_status_authentication() {
+        let body = r#"{"type": "error", "error": {"type": "authentication_error", "message": "invalid x-api-key"}}"#;
+        match AnthropicError::from_status(401, body) {
+            AnthropicError::AuthenticationError(message) => {
+                assert_eq!(message, "invalid x-api-key")
+            }
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_from_status_rate_limit_with_retry_after() {
+        let body = r#"{"type": "error", "error": {"type": "rate_limit_error", "message": "Number of request tokens has exceeded your per-minute rate limit"}}"#;
+        let error = AnthropicError::from_status(429, body).with_retry_after(30);
+        assert!(matches!(
+            error,
+            AnthropicError::RateLimitExceeded {
+                retry_after: Some(30)
+            }
+        ));
+    }
+
+    #[test]
+    fn test_from_status_server_error() {
+        match AnthropicError::from_status(500, "upstream connect error") {
+            AnthropicError::ApiError { status, message } => {
+                assert_eq!(status, 500);
+                assert_eq!(message, "upstream connect error");
+            }
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+
+    fn envelope(error_type: &str, message: &str) -> String {
+        serde_json::to_string(&ApiErrorBody {
+            body_type: "error".to_string(),
+            error: ApiErrorDetail {
+                error_type: error_type.to_string(),
+                message: message.to_string(),
+            },
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_from_response_body_mappings() {
+        assert!(matches!(
+            AnthropicError::from_response_body(
+                401,
+                &envelope("authentication_error", "invalid x-api-key")
+            ),
+            AnthropicError::AuthenticationError(_)
+        ));
+        assert!(matches!(
+            AnthropicError::from_response_body(429, &envelope("rate_limit_error", "slow down")),
+            AnthropicError::RateLimitExceeded { retry_after: None }
+        ));
+        assert!(matches!(
+            AnthropicError::from_response_body(529, &envelope("overloaded_error", "Overloaded")),
+            AnthropicError::Overloaded
+        ));
+        match AnthropicError::from_response_body(
+            400,
+            &envelope("invalid_request_error", "max_tokens: Field required"),
+        ) {
+            AnthropicError::ApiError { status, message } => {
+                assert_eq!(status, 400);
+                assert_eq!(message, "max_tokens: Field required");
+            }
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_from_response_body_without_envelope() {
+        assert!(matches!(
+            AnthropicError::from_response_body(529, "<html>overloaded</html>"),
+            AnthropicError::Overloaded
+        ));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(AnthropicError::RateLimitExceeded { retry_after: None }.is_retryable());
+        assert!(AnthropicError::Overloaded.is_retryable());
+        assert!(AnthropicError::ApiError {
+            status: 503,
+            message: "unavailable".to_string()
+        }
+        .is_retryable());
+        assert!(!AnthropicError::ApiError {
+            status: 400,
+            message: "bad request".to_string()
+        }
+        .is_retryable());
+        assert!(!AnthropicError::http("connection reset").is_retryable());
+        let json_error: AnthropicError = serde_json::from_str::<u32>("").unwrap_err().into();
+        assert!(!json_error.is_retryable());
+        assert!(!AnthropicError::InvalidResponse("missing id".to_string()).is_retryable());
+        assert!(!AnthropicError::AuthenticationError("bad key".to_string()).is_retryable());
+    }
+
+    #[test]
+    fn test_retry_after() {
+        assert_eq!(
+            AnthropicError::RateLimitExceeded {
+                retry_after: Some(12)
+            }
+            .retry_after(),
+            Some(Duration::from_secs(12))
+        );
+        assert_eq!(
+            AnthropicError::RateLimitExceeded { retry_after: None }.retry_after(),
+            None
+        );
+        assert_eq!(AnthropicError::Overloaded.retry_after(), None);
+    }
+
+    #[test]
+    fn test_error_source_chaining() {
+        let json_error = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let expected = json_error.to_string();
+        let error = AnthropicError::from(json_error);
+
+        assert_eq!(error.to_string(), format!("JSON error: {}", expected));
+        let source = error.source().expect("JsonError should have a source");
+        let source = source
+            .downcast_ref::<serde_json::Error>()
+            .expect("source should be a serde_json::Error");
+        assert!(source.is_eof());
+
+        let io_error = std::io::Error::new(std::io::ErrorKind::TimedOut, "timed out");
+        let error = AnthropicError::http_with_source(io_error);
+        assert_eq!(error.to_string(), "HTTP error: timed out");
+        assert!(error.source().unwrap().is::<std::io::Error>());
+
+        assert!(AnthropicError::http("no route").source().is_none());
     }
 }
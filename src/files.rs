@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+/// Metadata about a file uploaded through the Files API
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileMetadata {
+    /// ID of the file, used to reference it in content block sources
+    pub id: String,
+
+    /// Object type, always "file"
+    #[serde(rename = "type")]
+    pub file_type: String,
+
+    /// Original name of the uploaded file
+    pub filename: String,
+
+    /// MIME type of the file
+    pub mime_type: String,
+
+    /// Size of the file in bytes
+    pub size_bytes: u64,
+
+    /// RFC 3339 time at which the file was created
+    pub created_at: String,
+
+    /// Whether the file can be downloaded
+    #[serde(default)]
+    pub downloadable: bool,
+}
+
+/// A page of files from the Files API
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileList {
+    /// Files in this page
+    pub data: Vec<FileMetadata>,
+
+    /// Whether there are more files after this page
+    pub has_more: bool,
+
+    /// ID of the first file in this page, for fetching the previous page
+    pub first_id: Option<String>,
+
+    /// ID of the last file in this page, for fetching the next page
+    pub last_id: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_file_metadata() {
+        let json = serde_json::json!({
+            "id": "file_011CNha8iCJcU1wXNR6q4V8w",
+            "type": "file",
+            "filename": "quarterly-report.pdf",
+            "mime_type": "application/pdf",
+            "size_bytes": 1024000,
+            "created_at": "2025-04-14T20:58:12.633Z",
+            "downloadable": false
+        });
+
+        let file: FileMetadata = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(file.filename, "quarterly-report.pdf");
+        assert_eq!(file.size_bytes, 1024000);
+        assert_eq!(serde_json::to_value(&file).unwrap(), json);
+    }
+
+    #[test]
+    fn test_round_trip_file_list() {
+        let json = serde_json::json!({
+            "data": [
+                {
+                    "id": "file_011CNha8iCJcU1wXNR6q4V8w",
+                    "type": "file",
+                    "filename": "report.pdf",
+                    "mime_type": "application/pdf",
+                    "size_bytes": 1024,
+                    "created_at": "2025-04-14T20:58:12.633Z",
+                    "downloadable": false
+                },
+                {
+                    "id": "file_011CPMxVD3fHLUhvTqtsQA5w",
+                    "type": "file",
+                    "filename": "chart.png",
+                    "mime_type": "image/png",
+                    "size_bytes": 2048,
+                    "created_at": "2025-04-15T09:12:00.000Z",
+                    "downloadable": true
+                }
+            ],
+            "has_more": true,
+            "first_id": "file_011CNha8iCJcU1wXNR6q4V8w",
+            "last_id": "file_011CPMxVD3fHLUhvTqtsQA5w"
+        });
+
+        let list: FileList = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(list.data.len(), 2);
+        assert!(list.has_more);
+        assert_eq!(
+            list.last_id.as_deref(),
+            Some("file_011CPMxVD3fHLUhvTqtsQA5w")
+        );
+        assert_eq!(serde_json::to_value(&list).unwrap(), json);
+
+        let empty: FileList = serde_json::from_str(
+            r#"{"data": [], "has_more": false, "first_id": null, "last_id": null}"#,
+        )
+        .unwrap();
+        assert!(empty.first_id.is_none());
+    }
+}
@@ -3,16 +3,31 @@
 // This crate provides type definitions for interacting with the Anthropic API
 // and is intended to be used by Theater actors that need to communicate with Claude.
 
+mod base64;
+pub mod batches;
 pub mod errors;
+pub mod files;
+#[cfg(feature = "sha2")]
+mod hash;
 pub mod messages;
 pub mod models;
+pub mod streaming;
 pub mod tool_choice;
+pub mod tools;
+pub mod vision;
 
 // Re-export main types for convenience
-pub use errors::AnthropicError;
+pub use batches::{BatchRequest, BatchResult, BatchResultLine, CreateBatchRequest, MessageBatch};
+pub use errors::{AnthropicError, ApiErrorBody};
+pub use files::{FileList, FileMetadata};
 pub use messages::{
-    AnthropicRequest, AnthropicResponse, CompletionRequest, CompletionResponse, Message,
-    MessageContent, ResponseStatus, Usage,
+    AnthropicRequest, AnthropicResponse, CompletionRequest, CompletionResponse, CountTokensRequest,
+    CountTokensResponse, Message, MessageContent, ResponseStatus, Role, Usage,
+};
+pub use models::{Model, ModelInfo, ModelPricing};
+pub use streaming::{
+    ContentBlockKind, ContentBlockStart, PartialCompletionResponse, ResponseAccumulator,
+    StreamEvent,
 };
-pub use models::{ModelInfo, ModelPricing};
 pub use tool_choice::ToolChoice;
+pub use tools::AnthropicTool;
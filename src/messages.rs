@@ -1,17 +1,46 @@
-use crate::models::ModelInfo;
+use crate::errors::AnthropicError;
+use crate::models::{ModelInfo, ModelPricing};
 use crate::tool_choice::ToolChoice;
-use mcp_protocol::tool::{Tool, ToolContent};
+use crate::tools::{AnthropicTool, McpServerConfig};
+use mcp_protocol::tool::ToolContent;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign};
+use std::str::FromStr;
 
 /// Cache control configuration for system messages
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct CacheControl {
     #[serde(rename = "type")]
     pub cache_type: String,
+
+    /// Cache lifetime, either "5m" or "1h" (defaults to "5m" when omitted)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<String>,
+}
+
+impl CacheControl {
+    /// Create an ephemeral cache breakpoint with the default TTL
+    pub fn ephemeral() -> Self {
+        Self {
+            cache_type: "ephemeral".to_string(),
+            ttl: None,
+        }
+    }
+
+    /// Create an ephemeral cache breakpoint with an explicit TTL
+    pub fn ephemeral_with_ttl(ttl: impl Into<String>) -> Self {
+        Self {
+            cache_type: "ephemeral".to_string(),
+            ttl: Some(ttl.into()),
+        }
+    }
 }
 
 /// A single system message with optional cache control
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct SystemMessage {
     #[serde(rename = "type")]
     pub message_type: String,
@@ -20,8 +49,27 @@ pub struct SystemMessage {
     pub cache_control: Option<CacheControl>,
 }
 
+impl SystemMessage {
+    /// Create a plain text system block
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            message_type: "text".to_string(),
+            text: text.into(),
+            cache_control: None,
+        }
+    }
+
+    /// Create a text system block marked as an ephemeral cache breakpoint
+    pub fn cached(text: impl Into<String>) -> Self {
+        Self {
+            cache_control: Some(CacheControl::ephemeral()),
+            ..Self::text(text)
+        }
+    }
+}
+
 /// Different types of system messages that can be provided
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum SystemMessageFormat {
     /// Simple string format
@@ -30,231 +78,4866 @@ pub enum SystemMessageFormat {
     Array(Vec<SystemMessage>),
 }
 
+impl SystemMessageFormat {
+    /// Append a text block, converting string content into an array first
+    pub fn push_text(&mut self, text: impl Into<String>, cache_control: Option<CacheControl>) {
+        let block = SystemMessage {
+            cache_control,
+            ..SystemMessage::text(text)
+        };
+        match self {
+            SystemMessageFormat::String(existing) => {
+                let existing = SystemMessage::text(std::mem::take(existing));
+                *self = SystemMessageFormat::Array(vec![existing, block]);
+            }
+            SystemMessageFormat::Array(blocks) => blocks.push(block),
+        }
+    }
+
+    /// Concatenate the text of the system prompt
+    pub fn as_text(&self) -> String {
+        match self {
+            SystemMessageFormat::String(text) => text.clone(),
+            SystemMessageFormat::Array(blocks) => {
+                blocks.iter().map(|block| block.text.as_str()).collect()
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SystemMessageFormat {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match serde_json::Value::deserialize(deserializer)? {
+            serde_json::Value::String(text) => Ok(SystemMessageFormat::String(text)),
+            value @ serde_json::Value::Array(_) => Vec::<SystemMessage>::deserialize(value)
+                .map(SystemMessageFormat::Array)
+                .map_err(serde::de::Error::custom),
+            other => Err(serde::de::Error::custom(format!(
+                "expected string or array of system blocks, got {}",
+                json_type_name(&other)
+            ))),
+        }
+    }
+}
+
+/// Name of a JSON value's type, for error messages
+pub(crate) fn json_type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "boolean",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "array",
+        serde_json::Value::Object(_) => "object",
+    }
+}
+
+impl From<String> for SystemMessageFormat {
+    fn from(text: String) -> Self {
+        SystemMessageFormat::String(text)
+    }
+}
+
+impl From<&str> for SystemMessageFormat {
+    fn from(text: &str) -> Self {
+        SystemMessageFormat::String(text.to_string())
+    }
+}
+
+impl From<&String> for SystemMessageFormat {
+    fn from(text: &String) -> Self {
+        SystemMessageFormat::String(text.clone())
+    }
+}
+
+impl From<Vec<SystemMessage>> for SystemMessageFormat {
+    fn from(blocks: Vec<SystemMessage>) -> Self {
+        SystemMessageFormat::Array(blocks)
+    }
+}
+
+/// MIME type of inline media
+///
+/// Types accepted by the API map to dedicated variants; anything else is
+/// preserved verbatim in `MediaType::Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MediaType {
+    Jpeg,
+    Png,
+    Gif,
+    Webp,
+    Pdf,
+    PlainText,
+    /// A media type not known to this crate
+    Other(String),
+}
+
+impl MediaType {
+    /// Get the MIME string as sent over the wire
+    pub fn as_str(&self) -> &str {
+        match self {
+            MediaType::Jpeg => "image/jpeg",
+            MediaType::Png => "image/png",
+            MediaType::Gif => "image/gif",
+            MediaType::Webp => "image/webp",
+            MediaType::Pdf => "application/pdf",
+            MediaType::PlainText => "text/plain",
+            MediaType::Other(media_type) => media_type,
+        }
+    }
+}
+
+impl From<&str> for MediaType {
+    fn from(media_type: &str) -> Self {
+        match media_type {
+            "image/jpeg" => MediaType::Jpeg,
+            "image/png" => MediaType::Png,
+            "image/gif" => MediaType::Gif,
+            "image/webp" => MediaType::Webp,
+            "application/pdf" => MediaType::Pdf,
+            "text/plain" => MediaType::PlainText,
+            other => MediaType::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for MediaType {
+    fn from(media_type: String) -> Self {
+        MediaType::from(media_type.as_str())
+    }
+}
+
+impl PartialEq<str> for MediaType {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl fmt::Display for MediaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for MediaType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for MediaType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(MediaType::from)
+    }
+}
+
+/// Source of an image content block
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum ImageSource {
+    /// Inline base64-encoded image data
+    #[serde(rename = "base64")]
+    Base64 { media_type: MediaType, data: String },
+
+    /// Image referenced by URL
+    #[serde(rename = "url")]
+    Url { url: String },
+
+    /// Image uploaded through the Files API
+    #[serde(rename = "file")]
+    File { file_id: String },
+}
+
+impl ImageSource {
+    /// Create an inline source by base64 encoding raw bytes
+    pub fn base64_from_bytes(media_type: impl Into<MediaType>, bytes: &[u8]) -> Self {
+        ImageSource::Base64 {
+            media_type: media_type.into(),
+            data: crate::base64::encode(bytes),
+        }
+    }
+
+    /// Decode the data of an inline base64 source
+    pub fn decode_base64(&self) -> Result<Vec<u8>, AnthropicError> {
+        match self {
+            ImageSource::Base64 { data, .. } => {
+                crate::base64::decode(data).map_err(AnthropicError::InvalidResponse)
+            }
+            _ => Err(AnthropicError::InvalidResponse(
+                "source is not inline base64 data".to_string(),
+            )),
+        }
+    }
+}
+
+/// Source of a document content block
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum DocumentSource {
+    /// Inline base64-encoded PDF
+    #[serde(rename = "base64")]
+    Base64 { media_type: MediaType, data: String },
+
+    /// Inline plain text
+    #[serde(rename = "text")]
+    Text { media_type: MediaType, data: String },
+
+    /// PDF referenced by URL
+    #[serde(rename = "url")]
+    Url { url: String },
+
+    /// Document uploaded through the Files API
+    #[serde(rename = "file")]
+    File { file_id: String },
+}
+
+impl DocumentSource {
+    /// Create an inline source by base64 encoding raw bytes
+    pub fn base64_from_bytes(media_type: impl Into<MediaType>, bytes: &[u8]) -> Self {
+        DocumentSource::Base64 {
+            media_type: media_type.into(),
+            data: crate::base64::encode(bytes),
+        }
+    }
+
+    /// Create an inline base64 PDF source from raw bytes
+    pub fn pdf(bytes: &[u8]) -> Self {
+        DocumentSource::base64_from_bytes(MediaType::Pdf, bytes)
+    }
+
+    /// Decode the data of an inline base64 source
+    pub fn decode_base64(&self) -> Result<Vec<u8>, AnthropicError> {
+        match self {
+            DocumentSource::Base64 { data, .. } => {
+                crate::base64::decode(data).map_err(AnthropicError::InvalidResponse)
+            }
+            _ => Err(AnthropicError::InvalidResponse(
+                "source is not inline base64 data".to_string(),
+            )),
+        }
+    }
+
+    /// Create an inline plain text source
+    pub fn text(text: impl Into<String>) -> Self {
+        DocumentSource::Text {
+            media_type: MediaType::PlainText,
+            data: text.into(),
+        }
+    }
+
+    /// Estimate the decoded size of base64 document data without decoding it
+    ///
+    /// Returns `None` for sources that are not inline base64 data.
+    pub fn estimated_size_bytes(&self) -> Option<usize> {
+        match self {
+            DocumentSource::Base64 { data, .. } => {
+                let encoded = data.trim_end_matches('=').len();
+                Some(encoded * 3 / 4)
+            }
+            _ => None,
+        }
+    }
+
+    /// Check the source before sending it, catching errors the API would reject
+    ///
+    /// URLs must be absolute `http` or `https` URLs with a host, base64 data
+    /// must be a PDF and inline text must be plain text.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            DocumentSource::Base64 { media_type, .. } if *media_type != MediaType::Pdf => {
+                Err(format!("unsupported document media type {}", media_type))
+            }
+            DocumentSource::Text { media_type, .. } if *media_type != MediaType::PlainText => {
+                Err(format!("unsupported text media type {}", media_type))
+            }
+            DocumentSource::Url { url } => validate_http_url(url),
+            DocumentSource::File { file_id } if file_id.is_empty() => {
+                Err("file_id must not be empty".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Lightweight check that `url` is an absolute http(s) URL with a valid host
+fn validate_http_url(url: &str) -> Result<(), String> {
+    let Some((scheme, rest)) = url.split_once("://") else {
+        return Err(format!("invalid URL {:?}: missing scheme", url));
+    };
+    if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+        return Err(format!(
+            "unsupported URL scheme {:?}, expected http or https",
+            scheme
+        ));
+    }
+    if url.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(format!("invalid URL {:?}: contains whitespace", url));
+    }
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    // Drop any userinfo before the host
+    let host_port = authority.rsplit('@').next().unwrap_or_default();
+    // A colon inside an IPv6 literal such as [::1] is not a port separator
+    let (host, port) = match host_port.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => (host, Some(port)),
+        _ => (host_port, None),
+    };
+    let valid_host = if let Some(ip) = host.strip_prefix('[') {
+        ip.strip_suffix(']').is_some_and(|ip| {
+            !ip.is_empty()
+                && ip
+                    .chars()
+                    .all(|c| c.is_ascii_hexdigit() || c == ':' || c == '.')
+        })
+    } else {
+        !host.is_empty()
+            && host.split('.').all(|label| {
+                !label.is_empty()
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+                    && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            })
+    };
+    if !valid_host {
+        return Err(format!("invalid URL {:?}: bad host", url));
+    }
+    if let Some(port) = port {
+        if port.parse::<u16>().is_err() {
+            return Err(format!("invalid URL {:?}: bad port", url));
+        }
+    }
+    Ok(())
+}
+
 /// Different types of content that can be in a message
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub enum MessageContent {
     #[serde(rename = "text")]
-    Text { text: String },
+    Text {
+        text: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        citations: Option<Vec<Citation>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
 
     #[serde(rename = "tool_use")]
     ToolUse {
         id: String,
         name: String,
         input: serde_json::Value,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
     },
 
     #[serde(rename = "tool_result")]
     ToolResult {
         tool_use_id: String,
+        #[serde(with = "tool_result_content")]
         content: Vec<ToolContent>,
         #[serde(skip_serializing_if = "Option::is_none")]
         is_error: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
     },
-}
 
-/// A single message in a conversation with Claude
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Message {
-    /// Role of the message sender (user, assistant, system)
-    pub role: String,
+    #[serde(rename = "image")]
+    Image {
+        source: ImageSource,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
 
-    /// Content of the message - can be a string or vector of MessageContent objects
-    pub content: MessageContentFormat,
+    /// A PDF or plain text document Claude can read and cite
+    #[serde(rename = "document")]
+    Document {
+        source: DocumentSource,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        /// Extra context about the document that Claude won't cite
+        #[serde(skip_serializing_if = "Option::is_none")]
+        context: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        citations: Option<DocumentCitations>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+
+    #[serde(rename = "thinking")]
+    Thinking {
+        thinking: String,
+        /// Empty at the start of a streamed thinking block until a signature delta arrives
+        #[serde(default)]
+        signature: String,
+    },
+
+    #[serde(rename = "redacted_thinking")]
+    RedactedThinking { data: String },
+
+    #[serde(rename = "code_execution_tool_result")]
+    CodeExecutionToolResult {
+        tool_use_id: String,
+        content: CodeExecutionResult,
+    },
+
+    #[serde(rename = "mcp_tool_use")]
+    McpToolUse {
+        id: String,
+        name: String,
+        server_name: String,
+        input: serde_json::Value,
+    },
+
+    #[serde(rename = "mcp_tool_result")]
+    McpToolResult {
+        tool_use_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        is_error: Option<bool>,
+        #[serde(with = "tool_result_content")]
+        content: Vec<ToolContent>,
+    },
+
+    /// A search result supplied by the caller, citable like a document
+    #[serde(rename = "search_result")]
+    SearchResult {
+        /// Where the result came from, usually a URL
+        source: String,
+        title: String,
+        content: Vec<ChunkedText>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        citations: Option<DocumentCitations>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(untagged)]
-pub enum MessageContentFormat {
-    /// Simple string format
-    String(String),
-    /// Structured content format
-    Structured(Vec<MessageContent>),
+/// A chunk of plain text inside a search result
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChunkedText {
+    #[serde(rename = "type")]
+    pub text_type: String,
+    pub text: String,
 }
 
-impl Message {
-    /// Create a new message with structured content
-    pub fn new_structured(role: impl Into<String>, content: Vec<MessageContent>) -> Self {
+impl ChunkedText {
+    /// Create a new text chunk
+    pub fn new(text: impl Into<String>) -> Self {
         Self {
-            role: role.into(),
-            content: MessageContentFormat::Structured(content),
+            text_type: "text".to_string(),
+            text: text.into(),
         }
     }
 }
 
-/// Request to generate a completion from Claude
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct CompletionRequest {
-    /// The Claude model to use
-    pub model: String,
+/// Whether Claude may cite a document or search result
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DocumentCitations {
+    pub enabled: bool,
+}
 
-    /// List of messages in the conversation
-    pub messages: Vec<Message>,
+/// Wire format of tool result content
+///
+/// MCP images are `{"type": "image", "data", "mimeType"}` while the Anthropic
+/// API expects `{"type": "image", "source": {"type": "base64", ...}}`, so images
+/// are translated in both directions. The API also allows the content to be a
+/// bare string, which is read as a single text item.
+mod tool_result_content {
+    use mcp_protocol::tool::ToolContent;
+    use serde::de::Error as _;
+    use serde::ser::SerializeSeq;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use serde_json::{json, Value};
 
-    /// Maximum number of tokens to generate
-    pub max_tokens: u32,
+    pub fn serialize<S: Serializer>(
+        content: &[ToolContent],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(content.len()))?;
+        for item in content {
+            match item {
+                ToolContent::Image { data, mime_type } => seq.serialize_element(&json!({
+                    "type": "image",
+                    "source": {"type": "base64", "media_type": mime_type, "data": data}
+                }))?,
+                other => seq.serialize_element(other)?,
+            }
+        }
+        seq.end()
+    }
 
-    /// Temperature parameter (0.0 to 1.0)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub temperature: Option<f32>,
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<ToolContent>, D::Error> {
+        let items = match Value::deserialize(deserializer)? {
+            Value::String(text) => return Ok(vec![ToolContent::Text { text }]),
+            Value::Array(items) => items,
+            _ => {
+                return Err(D::Error::custom(
+                    "expected string or array of tool result content",
+                ))
+            }
+        };
+        items
+            .into_iter()
+            .map(|item| match (&item["type"], &item["source"]) {
+                (Value::String(kind), Value::Object(source)) if kind == "image" => {
+                    let field = |name: &str| {
+                        source
+                            .get(name)
+                            .and_then(Value::as_str)
+                            .map(str::to_string)
+                            .ok_or_else(|| {
+                                D::Error::custom(format!("image source is missing `{}`", name))
+                            })
+                    };
+                    Ok(ToolContent::Image {
+                        data: field("data")?,
+                        mime_type: field("media_type")?,
+                    })
+                }
+                _ => ToolContent::deserialize(item).map_err(D::Error::custom),
+            })
+            .collect()
+    }
+}
 
-    /// System prompt to use (can be a string or array of structured messages)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub system: Option<SystemMessageFormat>,
+/// Result of running code with the code execution tool
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum CodeExecutionResult {
+    /// The code ran, possibly with a non-zero return code
+    #[serde(rename = "code_execution_result")]
+    Result {
+        stdout: String,
+        stderr: String,
+        return_code: i32,
+        /// Files produced by the execution
+        #[serde(default)]
+        content: Vec<CodeExecutionOutput>,
+    },
 
-    /// Tools to make available to Claude
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub tools: Option<Vec<Tool>>,
+    /// The code could not be run
+    #[serde(rename = "code_execution_tool_result_error")]
+    Error { error_code: String },
+}
 
-    /// Tool choice configuration
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub tool_choice: Option<ToolChoice>,
+/// A file produced by the code execution tool
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CodeExecutionOutput {
+    /// Output type, always "code_execution_output"
+    #[serde(rename = "type")]
+    pub output_type: String,
 
-    /// Whether to disable parallel tool use
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub disable_parallel_tool_use: Option<bool>,
+    /// ID of the file in the Files API
+    pub file_id: String,
 }
 
-/// Information about token usage
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Usage {
-    pub input_tokens: u32,
+// mcp_protocol's ToolContent does not implement PartialEq, so content blocks are
+// compared by their JSON representation.
+impl PartialEq for MessageContent {
+    fn eq(&self, other: &Self) -> bool {
+        match (serde_json::to_value(self), serde_json::to_value(other)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => false,
+        }
+    }
+}
 
-    pub output_tokens: u32,
+impl MessageContent {
+    /// Create a new text content block
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text {
+            text: text.into(),
+            citations: None,
+            cache_control: None,
+        }
+    }
 
-    pub cache_read_input_tokens: Option<u32>,
+    /// Create a tool_result block holding a single text item
+    pub fn tool_result(tool_use_id: impl Into<String>, text: impl Into<String>) -> Self {
+        Self::ToolResult {
+            tool_use_id: tool_use_id.into(),
+            content: vec![ToolContent::Text { text: text.into() }],
+            is_error: None,
+            cache_control: None,
+        }
+    }
 
-    pub cache_creation_input_tokens: Option<u32>,
-}
+    /// Create a tool_result block reporting that the tool call failed
+    pub fn tool_error(tool_use_id: impl Into<String>, text: impl Into<String>) -> Self {
+        Self::ToolResult {
+            tool_use_id: tool_use_id.into(),
+            content: vec![ToolContent::Text { text: text.into() }],
+            is_error: Some(true),
+            cache_control: None,
+        }
+    }
 
-/// Response from a completion request
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct CompletionResponse {
-    /// Generated content blocks
-    pub content: Vec<MessageContent>,
+    /// Create a tool_result block from arbitrary tool content
+    ///
+    /// The API accepts text and image items in a tool result. Images are sent
+    /// as base64 sources; audio and resource items have no API equivalent and
+    /// are rejected by the API.
+    pub fn tool_result_blocks(
+        tool_use_id: impl Into<String>,
+        content: Vec<ToolContent>,
+        is_error: Option<bool>,
+    ) -> Self {
+        Self::ToolResult {
+            tool_use_id: tool_use_id.into(),
+            content,
+            is_error,
+            cache_control: None,
+        }
+    }
 
-    /// ID of the message
-    pub id: String,
+    /// Concatenate the text items of a tool_result block
+    ///
+    /// Returns `None` for blocks that are not tool results.
+    pub fn tool_result_text(&self) -> Option<String> {
+        match self {
+            MessageContent::ToolResult { content, .. } => Some(
+                content
+                    .iter()
+                    .filter_map(|item| match item {
+                        ToolContent::Text { text } => Some(text.as_str()),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
 
-    /// Model used for generation
-    pub model: String,
+    /// Maximum size of an inline document accepted by the API
+    pub const MAX_DOCUMENT_BYTES: usize = 32 * 1024 * 1024;
 
-    // always "assistant"
-    pub role: String,
+    /// Check that an inline base64 document is within the API's size limit
+    ///
+    /// Blocks other than base64 documents are always valid. The API's 100 page
+    /// limit is not checked, since that needs the PDF to be parsed.
+    pub fn validate_document(&self) -> Result<(), String> {
+        if let MessageContent::Document { source, .. } = self {
+            if let Some(size) = source.estimated_size_bytes() {
+                if size > Self::MAX_DOCUMENT_BYTES {
+                    return Err(format!(
+                        "document is {} bytes, the maximum is {} bytes",
+                        size,
+                        Self::MAX_DOCUMENT_BYTES
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
 
-    /// Reason why generation stopped
-    /// can be "end_turn", "max_tokens", "stop_sequence", "tool_use", null
-    pub stop_reason: StopReason,
+    /// Get the cache breakpoint on this block, if any
+    pub fn cache_control(&self) -> Option<&CacheControl> {
+        match self {
+            MessageContent::Text { cache_control, .. }
+            | MessageContent::ToolUse { cache_control, .. }
+            | MessageContent::ToolResult { cache_control, .. }
+            | MessageContent::Image { cache_control, .. }
+            | MessageContent::Document { cache_control, .. }
+            | MessageContent::SearchResult { cache_control, .. } => cache_control.as_ref(),
+            _ => None,
+        }
+    }
 
-    /// Stop sequence if applicable (deprecated - kept for backward compatibility)
-    pub stop_sequence: Option<String>,
+    /// Set the cache breakpoint on this block
+    ///
+    /// Returns `false`, leaving the block unchanged, for block types that cannot carry one.
+    pub fn set_cache_control(&mut self, value: Option<CacheControl>) -> bool {
+        match self {
+            MessageContent::Text { cache_control, .. }
+            | MessageContent::ToolUse { cache_control, .. }
+            | MessageContent::ToolResult { cache_control, .. }
+            | MessageContent::Image { cache_control, .. }
+            | MessageContent::Document { cache_control, .. }
+            | MessageContent::SearchResult { cache_control, .. } => {
+                *cache_control = value;
+                true
+            }
+            _ => false,
+        }
+    }
 
-    /// Message type
-    #[serde(rename = "type")]
-    pub message_type: String,
+    /// Rough size of the block's text and tool payloads, in characters
+    fn approx_char_count(&self) -> usize {
+        let tool_content_chars = |content: &[ToolContent]| -> usize {
+            content
+                .iter()
+                .map(|item| match item {
+                    ToolContent::Text { text } => text.chars().count(),
+                    _ => 0,
+                })
+                .sum()
+        };
+        match self {
+            MessageContent::Text { text, .. } => text.chars().count(),
+            MessageContent::Thinking { thinking, .. } => thinking.chars().count(),
+            MessageContent::ToolUse { name, input, .. }
+            | MessageContent::McpToolUse { name, input, .. } => {
+                name.chars().count() + input.to_string().chars().count()
+            }
+            MessageContent::ToolResult { content, .. }
+            | MessageContent::McpToolResult { content, .. } => tool_content_chars(content),
+            MessageContent::Document {
+                source: DocumentSource::Text { data, .. },
+                ..
+            } => data.chars().count(),
+            MessageContent::SearchResult { title, content, .. } => {
+                title.chars().count()
+                    + content
+                        .iter()
+                        .map(|chunk| chunk.text.chars().count())
+                        .sum::<usize>()
+            }
+            _ => 0,
+        }
+    }
 
-    /// Token usage information
-    pub usage: Usage,
+    /// Deserialize the input of a `tool_use` block into a typed parameters struct
+    pub fn parse_tool_input<T: DeserializeOwned>(&self) -> Result<T, AnthropicError> {
+        match self {
+            MessageContent::ToolUse { input, .. } => Ok(T::deserialize(input)?),
+            _ => Err(AnthropicError::InvalidResponse(
+                "expected a tool_use block".to_string(),
+            )),
+        }
+    }
 }
 
-/// Reason why generation stopped
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum StopReason {
+/// A citation supporting a span of generated text
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum Citation {
+    /// Character range within a plain text document
+    #[serde(rename = "char_location")]
+    CharLocation {
+        cited_text: String,
+        document_index: usize,
+        document_title: Option<String>,
+        start_char_index: usize,
+        end_char_index: usize,
+    },
+
+    /// Page range within a PDF document
+    #[serde(rename = "page_location")]
+    PageLocation {
+        cited_text: String,
+        document_index: usize,
+        document_title: Option<String>,
+        start_page_number: usize,
+        end_page_number: usize,
+    },
+
+    /// Content block range within a custom content document
+    #[serde(rename = "content_block_location")]
+    ContentBlockLocation {
+        cited_text: String,
+        document_index: usize,
+        document_title: Option<String>,
+        start_block_index: usize,
+        end_block_index: usize,
+    },
+}
+
+/// Role of a message sender
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Assistant,
+    System,
+}
+
+impl Role {
+    /// Get the wire representation of the role
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::System => "system",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(Role::User),
+            "assistant" => Ok(Role::Assistant),
+            "system" => Ok(Role::System),
+            other => Err(format!("Unknown role: {}", other)),
+        }
+    }
+}
+
+impl TryFrom<&str> for Role {
+    type Error = String;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl TryFrom<String> for Role {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// A single message in a conversation with Claude
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Message {
+    /// Role of the message sender (user, assistant, system)
+    pub role: Role,
+
+    /// Content of the message - can be a string or vector of MessageContent objects
+    pub content: MessageContentFormat,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MessageContentFormat {
+    /// Simple string format
+    String(String),
+    /// Structured content format
+    Structured(Vec<MessageContent>),
+}
+
+// Deserialized by hand rather than with `#[serde(untagged)]` so that a bad
+// payload reports what was wrong instead of "did not match any variant"
+impl<'de> Deserialize<'de> for MessageContentFormat {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match serde_json::Value::deserialize(deserializer)? {
+            serde_json::Value::String(text) => Ok(MessageContentFormat::String(text)),
+            value @ serde_json::Value::Array(_) => Vec::<MessageContent>::deserialize(value)
+                .map(MessageContentFormat::Structured)
+                .map_err(serde::de::Error::custom),
+            other => Err(serde::de::Error::custom(format!(
+                "expected string or array of content blocks, got {}",
+                json_type_name(&other)
+            ))),
+        }
+    }
+}
+
+impl From<String> for MessageContentFormat {
+    fn from(text: String) -> Self {
+        MessageContentFormat::String(text)
+    }
+}
+
+impl From<&str> for MessageContentFormat {
+    fn from(text: &str) -> Self {
+        MessageContentFormat::String(text.to_string())
+    }
+}
+
+impl From<&String> for MessageContentFormat {
+    fn from(text: &String) -> Self {
+        MessageContentFormat::String(text.clone())
+    }
+}
+
+impl From<Vec<MessageContent>> for MessageContentFormat {
+    fn from(blocks: Vec<MessageContent>) -> Self {
+        MessageContentFormat::Structured(blocks)
+    }
+}
+
+impl Message {
+    /// Create a new user message with plain text content
+    pub fn user(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::User,
+            content: MessageContentFormat::String(text.into()),
+        }
+    }
+
+    /// Create a new assistant message with plain text content
+    pub fn assistant(text: impl Into<String>) -> Self {
+        Self {
+            role: Role::Assistant,
+            content: MessageContentFormat::String(text.into()),
+        }
+    }
+
+    /// Create a new user message with structured content
+    pub fn user_with_blocks(content: Vec<MessageContent>) -> Self {
+        Self::new_structured(Role::User, content)
+    }
+
+    /// Create a new message with structured content
+    pub fn new_structured(role: impl Into<Role>, content: Vec<MessageContent>) -> Self {
+        Self {
+            role: role.into(),
+            content: MessageContentFormat::Structured(content),
+        }
+    }
+
+    /// Get the content as blocks, turning plain string content into a single text block
+    pub fn content_blocks(&self) -> Vec<MessageContent> {
+        match &self.content {
+            MessageContentFormat::String(text) => vec![MessageContent::text(text.as_str())],
+            MessageContentFormat::Structured(blocks) => blocks.clone(),
+        }
+    }
+
+    /// Convert plain string content into a single text block
+    ///
+    /// Messages that already have structured content are returned unchanged.
+    pub fn into_structured(self) -> Message {
+        match self.content {
+            MessageContentFormat::String(text) => {
+                Message::new_structured(self.role, vec![MessageContent::text(text)])
+            }
+            MessageContentFormat::Structured(_) => self,
+        }
+    }
+
+    /// Concatenate the text of all text content
+    pub fn text(&self) -> String {
+        match &self.content {
+            MessageContentFormat::String(text) => text.clone(),
+            MessageContentFormat::Structured(blocks) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    MessageContent::Text { text, .. } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Get `(tool_use_id, is_error)` for every tool result block
+    pub fn tool_results(&self) -> Vec<(&str, bool)> {
+        match &self.content {
+            MessageContentFormat::String(_) => Vec::new(),
+            MessageContentFormat::Structured(blocks) => blocks
+                .iter()
+                .filter_map(|block| match block {
+                    MessageContent::ToolResult {
+                        tool_use_id,
+                        is_error,
+                        ..
+                    } => Some((tool_use_id.as_str(), *is_error == Some(true))),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Get the `tool_use_id` of every tool result that reported an error
+    pub fn tool_result_errors(&self) -> Vec<&str> {
+        self.tool_results()
+            .into_iter()
+            .filter_map(|(id, is_error)| is_error.then_some(id))
+            .collect()
+    }
+
+    /// Rough size of the message in characters, counting text and tool payloads
+    ///
+    /// Images and other binary content are not counted.
+    pub fn approx_char_count(&self) -> usize {
+        match &self.content {
+            MessageContentFormat::String(text) => text.chars().count(),
+            MessageContentFormat::Structured(blocks) => {
+                blocks.iter().map(MessageContent::approx_char_count).sum()
+            }
+        }
+    }
+}
+
+/// Extended thinking configuration
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum ThinkingConfig {
+    /// Enable extended thinking with a token budget
+    #[serde(rename = "enabled")]
+    Enabled {
+        /// Maximum number of tokens Claude may use for thinking
+        budget_tokens: u32,
+    },
+
+    /// Disable extended thinking
+    #[serde(rename = "disabled")]
+    Disabled,
+}
+
+impl ThinkingConfig {
+    /// Create a new enabled thinking configuration
+    pub fn enabled(budget_tokens: u32) -> Self {
+        Self::Enabled { budget_tokens }
+    }
+
+    /// Create a new disabled thinking configuration
+    pub fn disabled() -> Self {
+        Self::Disabled
+    }
+}
+
+/// Service tier to use for a request
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceTier {
+    /// Use priority capacity when available, falling back to standard
+    Auto,
+
+    /// Only use standard capacity
+    StandardOnly,
+}
+
+/// Metadata about a request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct RequestMetadata {
+    /// Opaque identifier for the end user on whose behalf the request is made
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+}
+
+/// Request to generate a completion from Claude
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "CompletionRequestWire")]
+pub struct CompletionRequest {
+    /// The Claude model to use
+    pub model: String,
+
+    /// List of messages in the conversation
+    pub messages: Vec<Message>,
+
+    /// Maximum number of tokens to generate
+    pub max_tokens: u32,
+
+    /// Custom sequences that will cause generation to stop
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop_sequences: Option<Vec<String>>,
+
+    /// Temperature parameter (0.0 to 1.0)
+    ///
+    /// The API rejects values outside this range; use `set_temperature` to validate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+
+    /// Nucleus sampling parameter (0.0 to 1.0)
+    ///
+    /// The API rejects values outside this range; use `set_top_p` to validate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+
+    /// Only sample from the top K options for each token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+
+    /// System prompt to use (can be a string or array of structured messages)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<SystemMessageFormat>,
+
+    /// Tools to make available to Claude
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<AnthropicTool>>,
+
+    /// Tool choice configuration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+
+    /// Whether to disable parallel tool use
+    ///
+    /// Never serialized. A legacy top-level value is moved into `tool_choice` when a
+    /// request is deserialized, and only kept here when there is no tool choice to
+    /// move it into.
+    #[deprecated(
+        note = "the API reads this from `tool_choice`; use `ToolChoice::with_disable_parallel_tool_use`"
+    )]
+    #[serde(skip_serializing)]
+    pub disable_parallel_tool_use: Option<bool>,
+
+    /// Extended thinking configuration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
+
+    /// Metadata about the request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<RequestMetadata>,
+
+    /// Service tier to use for the request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<ServiceTier>,
+
+    /// ID of a code execution container to reuse from a previous response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+
+    /// Remote MCP servers Claude can call tools on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mcp_servers: Option<Vec<McpServerConfig>>,
+}
+
+/// Wire form of [`CompletionRequest`], which may carry the legacy top-level
+/// `disable_parallel_tool_use`
+#[derive(Deserialize)]
+struct CompletionRequestWire {
+    model: String,
+    messages: Vec<Message>,
+    max_tokens: u32,
+    stop_sequences: Option<Vec<String>>,
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    top_k: Option<u32>,
+    system: Option<SystemMessageFormat>,
+    tools: Option<Vec<AnthropicTool>>,
+    tool_choice: Option<ToolChoice>,
+    disable_parallel_tool_use: Option<bool>,
+    thinking: Option<ThinkingConfig>,
+    metadata: Option<RequestMetadata>,
+    service_tier: Option<ServiceTier>,
+    container: Option<String>,
+    mcp_servers: Option<Vec<McpServerConfig>>,
+}
+
+impl From<CompletionRequestWire> for CompletionRequest {
+    fn from(wire: CompletionRequestWire) -> Self {
+        let has_tools = wire.tools.as_ref().is_some_and(|tools| !tools.is_empty());
+        let (tool_choice, legacy) = match (wire.tool_choice, wire.disable_parallel_tool_use) {
+            // A flag already on the choice wins over the legacy one
+            (Some(choice), Some(_)) if choice.disable_parallel_tool_use().is_some() => {
+                (Some(choice), None)
+            }
+            (Some(choice), Some(disable)) => {
+                (Some(choice.with_disable_parallel_tool_use(disable)), None)
+            }
+            // The API defaults to auto when tools are given and no choice is set
+            (None, Some(disable)) if has_tools => (
+                Some(ToolChoice::auto().with_disable_parallel_tool_use(disable)),
+                None,
+            ),
+            other => other,
+        };
+
+        #[allow(deprecated)]
+        CompletionRequest {
+            model: wire.model,
+            messages: wire.messages,
+            max_tokens: wire.max_tokens,
+            stop_sequences: wire.stop_sequences,
+            temperature: wire.temperature,
+            top_p: wire.top_p,
+            top_k: wire.top_k,
+            system: wire.system,
+            tools: wire.tools,
+            tool_choice,
+            disable_parallel_tool_use: legacy,
+            thinking: wire.thinking,
+            metadata: wire.metadata,
+            service_tier: wire.service_tier,
+            container: wire.container,
+            mcp_servers: wire.mcp_servers,
+        }
+    }
+}
+
+impl CompletionRequest {
+    /// Start building a new completion request
+    pub fn builder() -> CompletionRequestBuilder {
+        CompletionRequestBuilder::default()
+    }
+
+    /// Set the system prompt to a single block marked as a cache breakpoint
+    pub fn with_cached_system(&mut self, prompt: impl Into<String>) {
+        self.system = Some(SystemMessageFormat::Array(vec![SystemMessage::cached(
+            prompt,
+        )]));
+    }
+
+    /// Append a system block marked as a cache breakpoint
+    ///
+    /// An existing string system prompt is kept as an uncached first block.
+    pub fn add_cached_system_block(&mut self, text: impl Into<String>) {
+        match &mut self.system {
+            Some(system) => system.push_text(text, Some(CacheControl::ephemeral())),
+            None => self.with_cached_system(text),
+        }
+    }
+
+    /// Mark the last content block of the last message as a cache breakpoint
+    ///
+    /// String content is converted to a single text block first. Trailing blocks that
+    /// cannot carry a breakpoint, such as thinking blocks, are skipped in favour of the
+    /// last block that can.
+    pub fn set_cache_breakpoint_on_last_block(&mut self) {
+        let Some(last) = self.messages.pop() else {
+            return;
+        };
+        let mut last = last.into_structured();
+        if let MessageContentFormat::Structured(blocks) = &mut last.content {
+            for block in blocks.iter_mut().rev() {
+                if block.set_cache_control(Some(CacheControl::ephemeral())) {
+                    break;
+                }
+            }
+        }
+        self.messages.push(last);
+    }
+
+    /// Rough size of the system prompt and messages in characters
+    pub fn approx_char_count(&self) -> usize {
+        let system = match &self.system {
+            Some(SystemMessageFormat::String(text)) => text.chars().count(),
+            Some(SystemMessageFormat::Array(blocks)) => {
+                blocks.iter().map(|block| block.text.chars().count()).sum()
+            }
+            None => 0,
+        };
+        system
+            + self
+                .messages
+                .iter()
+                .map(Message::approx_char_count)
+                .sum::<usize>()
+    }
+
+    /// Convert every message to structured content, see [`Message::into_structured`]
+    pub fn normalize_content(&mut self) {
+        self.messages = std::mem::take(&mut self.messages)
+            .into_iter()
+            .map(Message::into_structured)
+            .collect();
+    }
+
+    /// Roughly estimate the input tokens of this request without calling the API
+    ///
+    /// This is a heuristic for local gating, not an exact count: text is
+    /// counted at four characters per token, images at a fixed cost close to
+    /// the API's maximum per image, and each message and tool adds a fixed
+    /// overhead. Use `CountTokensRequest` when an exact number matters.
+    #[cfg(feature = "token-estimate")]
+    pub fn estimate_tokens(&self) -> u32 {
+        const REQUEST_OVERHEAD: usize = 3;
+        const MESSAGE_OVERHEAD: usize = 4;
+        const IMAGE_TOKENS: usize = crate::vision::MAX_IMAGE_TOKENS as usize;
+        const TOOL_OVERHEAD: usize = 20;
+        // Tool use adds a system prompt describing how to call tools
+        const TOOL_USE_SYSTEM_PROMPT: usize = 300;
+
+        let text_tokens = |chars: usize| chars.div_ceil(4);
+
+        let system = match &self.system {
+            Some(system) => text_tokens(system.as_text().chars().count()),
+            None => 0,
+        };
+        let messages: usize = self
+            .messages
+            .iter()
+            .map(|message| {
+                let images = match &message.content {
+                    MessageContentFormat::String(_) => 0,
+                    MessageContentFormat::Structured(blocks) => blocks
+                        .iter()
+                        .filter(|block| matches!(block, MessageContent::Image { .. }))
+                        .count(),
+                };
+                MESSAGE_OVERHEAD + text_tokens(message.approx_char_count()) + images * IMAGE_TOKENS
+            })
+            .sum();
+        let tools = match &self.tools {
+            Some(tools) if !tools.is_empty() => {
+                TOOL_USE_SYSTEM_PROMPT
+                    + tools
+                        .iter()
+                        .map(|tool| {
+                            let definition = serde_json::to_string(tool).unwrap_or_default();
+                            TOOL_OVERHEAD + text_tokens(definition.chars().count())
+                        })
+                        .sum::<usize>()
+            }
+            _ => 0,
+        };
+
+        u32::try_from(REQUEST_OVERHEAD + system + messages + tools).unwrap_or(u32::MAX)
+    }
+
+    /// Drop the oldest turns so that at most `max_messages` remain
+    ///
+    /// The first message is always kept since it usually carries the task
+    /// framing, so a `max_messages` of 0 behaves like 1. The retained tail
+    /// starts on an assistant turn so roles keep alternating and no tool
+    /// result is separated from its tool use.
+    pub fn truncate_history(&mut self, max_messages: usize) {
+        let max_messages = max_messages.max(1);
+        if self.messages.len() <= max_messages {
+            return;
+        }
+
+        let mut tail_start = self.messages.len() - (max_messages - 1);
+        while tail_start < self.messages.len() && self.messages[tail_start].role != Role::Assistant
+        {
+            tail_start += 1;
+        }
+        self.messages.drain(1..tail_start);
+    }
+
+    /// Serialize to compact JSON with object keys sorted at every level
+    ///
+    /// The output is stable across runs and independent of map iteration order,
+    /// so it can be hashed for cache keys or used to deduplicate batch requests.
+    pub fn to_canonical_json(&self) -> Result<String, AnthropicError> {
+        let value = sort_keys(serde_json::to_value(self)?);
+        Ok(serde_json::to_string(&value)?)
+    }
+
+    /// Hex SHA-256 of [`to_canonical_json`](Self::to_canonical_json), for memoizing completions
+    #[cfg(feature = "sha2")]
+    pub fn cache_key(&self) -> String {
+        // Serializing a request to a Value cannot fail: every map key is a string
+        let json = self
+            .to_canonical_json()
+            .expect("request serializes to JSON");
+        crate::hash::sha256_hex(json.as_bytes())
+    }
+
+    /// Set the temperature, rejecting values outside 0.0 to 1.0
+    pub fn set_temperature(&mut self, temperature: f32) -> Result<(), String> {
+        self.temperature = Some(validate_unit_range("temperature", temperature)?);
+        Ok(())
+    }
+
+    /// Set top_p, rejecting values outside 0.0 to 1.0
+    pub fn set_top_p(&mut self, top_p: f32) -> Result<(), String> {
+        self.top_p = Some(validate_unit_range("top_p", top_p)?);
+        Ok(())
+    }
+
+    /// Maximum number of cache breakpoints allowed in a single request
+    pub const MAX_CACHE_BREAKPOINTS: usize = 4;
+
+    /// Count the `cache_control` breakpoints across tools, system and messages
+    pub fn count_cache_breakpoints(&self) -> usize {
+        self.cache_controls().len()
+    }
+
+    /// Every `cache_control` set on tools, system blocks and message blocks
+    fn cache_controls(&self) -> Vec<&CacheControl> {
+        let tools = self
+            .tools
+            .iter()
+            .flatten()
+            .filter_map(AnthropicTool::cache_control);
+        let system = match &self.system {
+            Some(SystemMessageFormat::Array(blocks)) => blocks.as_slice(),
+            _ => &[],
+        }
+        .iter()
+        .filter_map(|block| block.cache_control.as_ref());
+        let messages = self
+            .messages
+            .iter()
+            .filter_map(|message| match &message.content {
+                MessageContentFormat::Structured(blocks) => Some(blocks),
+                MessageContentFormat::String(_) => None,
+            })
+            .flatten()
+            .filter_map(MessageContent::cache_control);
+
+        tools.chain(system).chain(messages).collect()
+    }
+
+    /// Text payloads longer than this many bytes are replaced by [`redacted`](Self::redacted)
+    pub const REDACT_TEXT_BYTES: usize = 256;
+
+    /// Copy of the request that is safe to log
+    ///
+    /// Base64 image and document data and MCP authorization tokens are always
+    /// replaced with `"<redacted N bytes>"`, as is any text longer than
+    /// [`REDACT_TEXT_BYTES`](Self::REDACT_TEXT_BYTES). The structure of the
+    /// request is otherwise unchanged.
+    pub fn redacted(&self) -> CompletionRequest {
+        fn redact(value: &mut String) {
+            *value = format!("<redacted {} bytes>", value.len());
+        }
+        fn redact_large(value: &mut String) {
+            if value.len() > CompletionRequest::REDACT_TEXT_BYTES {
+                redact(value);
+            }
+        }
+
+        let mut request = self.clone();
+        match &mut request.system {
+            Some(SystemMessageFormat::String(text)) => redact_large(text),
+            Some(SystemMessageFormat::Array(blocks)) => {
+                for block in blocks {
+                    redact_large(&mut block.text);
+                }
+            }
+            None => {}
+        }
+        for server in request.mcp_servers.iter_mut().flatten() {
+            if let Some(token) = &mut server.authorization_token {
+                redact(token);
+            }
+        }
+        for message in &mut request.messages {
+            let blocks = match &mut message.content {
+                MessageContentFormat::String(text) => {
+                    redact_large(text);
+                    continue;
+                }
+                MessageContentFormat::Structured(blocks) => blocks,
+            };
+            for block in blocks {
+                match block {
+                    MessageContent::Text { text, .. } => redact_large(text),
+                    MessageContent::Thinking { thinking, .. } => redact_large(thinking),
+                    MessageContent::SearchResult { content, .. } => {
+                        for chunk in content {
+                            redact_large(&mut chunk.text);
+                        }
+                    }
+                    MessageContent::Image {
+                        source: ImageSource::Base64 { data, .. },
+                        ..
+                    }
+                    | MessageContent::Document {
+                        source: DocumentSource::Base64 { data, .. },
+                        ..
+                    } => redact(data),
+                    MessageContent::Document {
+                        source: DocumentSource::Text { data, .. },
+                        ..
+                    } => redact_large(data),
+                    MessageContent::ToolResult { content, .. }
+                    | MessageContent::McpToolResult { content, .. } => {
+                        for item in content {
+                            match item {
+                                ToolContent::Text { text } => redact_large(text),
+                                ToolContent::Image { data, .. } => redact(data),
+                                _ => {}
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        request
+    }
+
+    /// Beta flags that must be sent in the `anthropic-beta` header for this request
+    ///
+    /// Detects the 1 hour cache TTL, Files API sources, the computer use tool,
+    /// MCP connectors and code execution containers. PDF documents are
+    /// generally available and need no beta of their own.
+    pub fn required_betas(&self) -> Vec<&'static str> {
+        let mut betas = Vec::new();
+
+        if self
+            .cache_controls()
+            .iter()
+            .any(|cache_control| cache_control.ttl.as_deref() == Some("1h"))
+        {
+            betas.push("extended-cache-ttl-2025-04-11");
+        }
+
+        let blocks = self
+            .messages
+            .iter()
+            .filter_map(|message| match &message.content {
+                MessageContentFormat::Structured(blocks) => Some(blocks),
+                MessageContentFormat::String(_) => None,
+            })
+            .flatten();
+        let mut uses_files = false;
+        let mut uses_code_execution = self.container.is_some();
+        for block in blocks {
+            match block {
+                MessageContent::Image {
+                    source: ImageSource::File { .. },
+                    ..
+                }
+                | MessageContent::Document {
+                    source: DocumentSource::File { .. },
+                    ..
+                } => uses_files = true,
+                MessageContent::CodeExecutionToolResult { .. } => uses_code_execution = true,
+                _ => {}
+            }
+        }
+        if uses_files {
+            betas.push("files-api-2025-04-14");
+        }
+        if uses_code_execution {
+            betas.push("code-execution-2025-05-22");
+        }
+
+        for tool in self.tools.iter().flatten() {
+            if let AnthropicTool::Builtin(tool) = tool {
+                if let Some(beta) = tool.tool_type().required_beta() {
+                    if !betas.contains(&beta) {
+                        betas.push(beta);
+                    }
+                }
+            }
+        }
+
+        if self
+            .mcp_servers
+            .as_ref()
+            .is_some_and(|servers| !servers.is_empty())
+        {
+            betas.push("mcp-client-2025-04-04");
+        }
+
+        betas
+    }
+
+    /// Check that the request does not exceed the cache breakpoint limit
+    pub fn validate_cache_breakpoints(&self) -> Result<(), String> {
+        let count = self.count_cache_breakpoints();
+        if count > Self::MAX_CACHE_BREAKPOINTS {
+            return Err(format!(
+                "request has {} cache breakpoints, at most {} are allowed",
+                count,
+                Self::MAX_CACHE_BREAKPOINTS
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that `max_tokens` does not exceed the model's output token limit
+    ///
+    /// Models without a known limit are not checked.
+    pub fn validate_max_tokens(&self) -> Result<(), String> {
+        let model_id = ModelInfo::resolve_alias(&self.model);
+        let Some(limit) = ModelInfo::known_max_output_tokens(model_id) else {
+            return Ok(());
+        };
+        if self.max_tokens > limit {
+            return Err(format!(
+                "max_tokens {} exceeds the {} output token limit of {}",
+                self.max_tokens, self.model, limit
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that a trailing assistant message, used as a prefill, does not end in whitespace
+    ///
+    /// Only the final content block is checked, and only if it is text. Requests whose
+    /// last message is not from the assistant are always valid.
+    pub fn validate_prefill(&self) -> Result<(), String> {
+        let Some(last) = self.messages.last() else {
+            return Ok(());
+        };
+        if last.role != Role::Assistant {
+            return Ok(());
+        }
+        // Only the final block is continued, so earlier blocks may end in whitespace
+        let text = match &last.content {
+            MessageContentFormat::String(text) => text.as_str(),
+            MessageContentFormat::Structured(blocks) => match blocks.last() {
+                Some(MessageContent::Text { text, .. }) => text.as_str(),
+                _ => return Ok(()),
+            },
+        };
+        if text.ends_with(char::is_whitespace) {
+            return Err(format!(
+                "assistant prefill must not end with whitespace: {:?}",
+                text
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that messages start with a user turn and alternate between user and assistant
+    ///
+    /// System prompts belong in `system`, so a `system` role message is an error.
+    pub fn validate_message_order(&self) -> Result<(), String> {
+        let Some(first) = self.messages.first() else {
+            return Err("at least one message is required".to_string());
+        };
+        if first.role != Role::User {
+            return Err(format!(
+                "the first message must be from the user, got {}",
+                first.role
+            ));
+        }
+        for (index, message) in self.messages.iter().enumerate() {
+            if message.role == Role::System {
+                return Err(format!(
+                    "message {} has role system; use the system field instead",
+                    index
+                ));
+            }
+            if index > 0 && self.messages[index - 1].role == message.role {
+                return Err(format!(
+                    "messages {} and {} are both from the {}",
+                    index - 1,
+                    index,
+                    message.role
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that `tool_choice` is consistent with `tools`
+    ///
+    /// A specific tool choice must name one of the defined tools, and any choice other
+    /// than `none` requires at least one tool. `none` is valid with or without tools.
+    pub fn validate_tool_choice(&self) -> Result<(), String> {
+        let Some(choice) = &self.tool_choice else {
+            return Ok(());
+        };
+        if matches!(choice, ToolChoice::None) {
+            return Ok(());
+        }
+        let tools = self.tools.as_deref().unwrap_or_default();
+        if tools.is_empty() {
+            return Err(format!("tool_choice {} requires at least one tool", choice));
+        }
+        if let ToolChoice::Tool { name, .. } = choice {
+            if !tools.iter().any(|tool| tool.name() == name) {
+                return Err(format!("tool_choice references unknown tool {:?}", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge runs of consecutive messages from the same role into a single message
+    ///
+    /// The content blocks of merged messages are concatenated in order, except that
+    /// tool results in a merged user turn are moved ahead of its other blocks, as the
+    /// API requires.
+    pub fn merge_consecutive_roles(&mut self) {
+        let mut merged: Vec<Message> = Vec::with_capacity(self.messages.len());
+        for message in std::mem::take(&mut self.messages) {
+            match merged.last_mut() {
+                Some(previous) if previous.role == message.role => {
+                    let mut blocks = previous.content_blocks();
+                    blocks.extend(message.content_blocks());
+                    if previous.role == Role::User {
+                        // Stable, so each group keeps its original order
+                        blocks.sort_by_key(|block| {
+                            !matches!(block, MessageContent::ToolResult { .. })
+                        });
+                    }
+                    previous.content = MessageContentFormat::Structured(blocks);
+                }
+                _ => merged.push(message),
+            }
+        }
+        self.messages = merged;
+    }
+
+    /// Get `(tool_use_id, is_error)` for every tool result in the conversation, oldest first
+    pub fn tool_results(&self) -> Vec<(&str, bool)> {
+        self.messages
+            .iter()
+            .flat_map(Message::tool_results)
+            .collect()
+    }
+
+    /// Append a message to the conversation
+    pub fn push_message(&mut self, message: Message) {
+        self.messages.push(message);
+    }
+
+    /// Append Claude's response to the conversation as an assistant turn
+    pub fn push_assistant_response(&mut self, response: &CompletionResponse) {
+        self.push_message(Message::from(response));
+    }
+
+    /// Append tool results to the conversation as a user turn
+    pub fn push_tool_results(&mut self, results: Vec<MessageContent>) {
+        self.push_message(Message::user_with_blocks(results));
+    }
+}
+
+/// Rebuild a JSON value with every object's keys in sorted order
+///
+/// `serde_json::Map` preserves insertion order when the `preserve_order`
+/// feature is enabled anywhere in the dependency graph, so sorting is explicit.
+fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sort_keys(value)))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(sort_keys).collect())
+        }
+        other => other,
+    }
+}
+
+fn validate_unit_range(name: &str, value: f32) -> Result<f32, String> {
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!(
+            "{} must be between 0.0 and 1.0, got {}",
+            name, value
+        ))
+    }
+}
+
+/// Builder for `CompletionRequest`
+#[derive(Debug, Clone, Default)]
+pub struct CompletionRequestBuilder {
+    model: Option<String>,
+    messages: Vec<Message>,
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+    system: Option<SystemMessageFormat>,
+    tools: Option<Vec<AnthropicTool>>,
+    tool_choice: Option<ToolChoice>,
+}
+
+impl CompletionRequestBuilder {
+    /// Set the model to use
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Set the maximum number of tokens to generate
+    pub fn max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Append a message to the conversation
+    pub fn message(mut self, message: Message) -> Self {
+        self.messages.push(message);
+        self
+    }
+
+    /// Append several messages to the conversation
+    pub fn messages(mut self, messages: impl IntoIterator<Item = Message>) -> Self {
+        self.messages.extend(messages);
+        self
+    }
+
+    /// Set the system prompt, either as a string or as a list of [`SystemMessage`] blocks
+    pub fn system(mut self, system: impl Into<SystemMessageFormat>) -> Self {
+        self.system = Some(system.into());
+        self
+    }
+
+    /// Set the temperature
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Set the tools available to Claude
+    pub fn tools(mut self, tools: Vec<AnthropicTool>) -> Self {
+        self.tools = Some(tools);
+        self
+    }
+
+    /// Set the tool choice configuration
+    pub fn tool_choice(mut self, tool_choice: ToolChoice) -> Self {
+        self.tool_choice = Some(tool_choice);
+        self
+    }
+
+    /// Build the request, checking that the required fields are set
+    pub fn build(self) -> Result<CompletionRequest, String> {
+        let model = self.model.ok_or("model is required")?;
+        let max_tokens = self.max_tokens.ok_or("max_tokens is required")?;
+        if self.messages.is_empty() {
+            return Err("at least one message is required".to_string());
+        }
+        if let Some(temperature) = self.temperature {
+            validate_unit_range("temperature", temperature)?;
+        }
+
+        #[allow(deprecated)]
+        Ok(CompletionRequest {
+            model,
+            messages: self.messages,
+            max_tokens,
+            stop_sequences: None,
+            temperature: self.temperature,
+            top_p: None,
+            top_k: None,
+            system: self.system,
+            tools: self.tools,
+            tool_choice: self.tool_choice,
+            disable_parallel_tool_use: None,
+            thinking: None,
+            metadata: None,
+            service_tier: None,
+            container: None,
+            mcp_servers: None,
+        })
+    }
+}
+
+/// Request to count the input tokens of a message without generating a response
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CountTokensRequest {
+    /// The Claude model to count tokens for
+    pub model: String,
+
+    /// List of messages in the conversation
+    pub messages: Vec<Message>,
+
+    /// System prompt to use (can be a string or array of structured messages)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<SystemMessageFormat>,
+
+    /// Tools to make available to Claude
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<AnthropicTool>>,
+
+    /// Tool choice configuration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+
+    /// Extended thinking configuration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<ThinkingConfig>,
+}
+
+impl From<&CompletionRequest> for CountTokensRequest {
+    fn from(request: &CompletionRequest) -> Self {
+        Self {
+            model: request.model.clone(),
+            messages: request.messages.clone(),
+            system: request.system.clone(),
+            tools: request.tools.clone(),
+            tool_choice: request.tool_choice.clone(),
+            thinking: request.thinking.clone(),
+        }
+    }
+}
+
+/// Response from a token counting request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CountTokensResponse {
+    /// Total number of input tokens
+    pub input_tokens: u32,
+}
+
+/// Information about token usage
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct Usage {
+    pub input_tokens: u32,
+
+    pub output_tokens: u32,
+
+    pub cache_read_input_tokens: Option<u32>,
+
+    pub cache_creation_input_tokens: Option<u32>,
+
+    /// Service tier that served the request ("standard", "priority" or "batch")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
+
+    /// Requests made by server-side tools such as web search
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_tool_use: Option<ServerToolUsage>,
+
+    /// Breakdown of `cache_creation_input_tokens` by cache TTL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_creation: Option<CacheCreation>,
+}
+
+/// Usage of server-side tools
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ServerToolUsage {
+    /// Number of web searches performed
+    #[serde(default)]
+    pub web_search_requests: u32,
+}
+
+/// Tokens written to the prompt cache, by TTL
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct CacheCreation {
+    /// Tokens written with the default 5 minute TTL
+    #[serde(default)]
+    pub ephemeral_5m_input_tokens: u32,
+
+    /// Tokens written with the 1 hour TTL
+    #[serde(default)]
+    pub ephemeral_1h_input_tokens: u32,
+}
+
+impl Usage {
+    /// Total input tokens, including tokens read from and written to the cache
+    pub fn total_input_tokens(&self) -> u32 {
+        self.input_tokens
+            .saturating_add(self.cache_read_input_tokens.unwrap_or(0))
+            .saturating_add(self.cache_creation_input_tokens.unwrap_or(0))
+    }
+
+    /// Total input and output tokens
+    pub fn total_tokens(&self) -> u32 {
+        self.total_input_tokens().saturating_add(self.output_tokens)
+    }
+
+    /// Estimate the cost in dollars of this usage under the given pricing
+    ///
+    /// Cache reads and writes use the pricing's cache rates when present, and are
+    /// otherwise derived from the input price. Cache writes with the 1 hour TTL, as
+    /// broken down in `cache_creation`, are priced at twice the input price.
+    pub fn estimated_cost(&self, pricing: &ModelPricing) -> f64 {
+        let cache_read = self.cache_read_input_tokens.unwrap_or(0) as f64;
+        let cache_creation_total = self.cache_creation_input_tokens.unwrap_or(0);
+        let cache_creation_1h = self
+            .cache_creation
+            .as_ref()
+            .map_or(0, |breakdown| breakdown.ephemeral_1h_input_tokens)
+            .min(cache_creation_total);
+        let cache_creation = (cache_creation_total - cache_creation_1h) as f64;
+
+        let total = self.input_tokens as f64 * pricing.input_cost_per_million_tokens
+            + self.output_tokens as f64 * pricing.output_cost_per_million_tokens
+            + cache_read * pricing.effective_cache_read_cost()
+            + cache_creation * pricing.effective_cache_write_cost()
+            + cache_creation_1h as f64 * pricing.effective_cache_write_1h_cost();
+
+        total / 1_000_000.0
+    }
+}
+
+fn add_optional(a: Option<u32>, b: Option<u32>) -> Option<u32> {
+    match (a, b) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or(0).saturating_add(b.unwrap_or(0))),
+    }
+}
+
+impl Add for Usage {
+    type Output = Usage;
+
+    fn add(mut self, other: Usage) -> Usage {
+        self += other;
+        self
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.input_tokens = self.input_tokens.saturating_add(other.input_tokens);
+        self.output_tokens = self.output_tokens.saturating_add(other.output_tokens);
+        self.cache_read_input_tokens =
+            add_optional(self.cache_read_input_tokens, other.cache_read_input_tokens);
+        self.cache_creation_input_tokens = add_optional(
+            self.cache_creation_input_tokens,
+            other.cache_creation_input_tokens,
+        );
+        if self.service_tier.is_none() {
+            self.service_tier = other.service_tier;
+        }
+        self.server_tool_use = match (self.server_tool_use.take(), other.server_tool_use) {
+            (Some(a), Some(b)) => Some(ServerToolUsage {
+                web_search_requests: a.web_search_requests.saturating_add(b.web_search_requests),
+            }),
+            (a, b) => a.or(b),
+        };
+        self.cache_creation = match (self.cache_creation.take(), other.cache_creation) {
+            (Some(a), Some(b)) => Some(CacheCreation {
+                ephemeral_5m_input_tokens: a
+                    .ephemeral_5m_input_tokens
+                    .saturating_add(b.ephemeral_5m_input_tokens),
+                ephemeral_1h_input_tokens: a
+                    .ephemeral_1h_input_tokens
+                    .saturating_add(b.ephemeral_1h_input_tokens),
+            }),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+impl Sum for Usage {
+    fn sum<I: Iterator<Item = Usage>>(iter: I) -> Usage {
+        iter.fold(Usage::default(), Add::add)
+    }
+}
+
+/// Response from a completion request
+///
+/// With the `strict` feature enabled, unknown fields are rejected instead of ignored.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct CompletionResponse {
+    /// Generated content blocks
+    pub content: Vec<MessageContent>,
+
+    /// ID of the message
+    pub id: String,
+
+    /// Model used for generation
+    pub model: String,
+
+    // always "assistant"
+    pub role: Role,
+
+    /// Reason why generation stopped
+    /// can be "end_turn", "max_tokens", "stop_sequence", "tool_use", "pause_turn", "refusal", null
+    pub stop_reason: Option<StopReason>,
+
+    /// The custom stop sequence that was matched, if `stop_reason` is `stop_sequence`
+    pub stop_sequence: Option<String>,
+
+    /// Message type
+    #[serde(rename = "type")]
+    pub message_type: String,
+
+    /// Token usage information
+    pub usage: Usage,
+
+    /// Code execution container used by the request, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<Container>,
+}
+
+/// Convert Claude's response into the assistant turn that continues the conversation
+impl From<&CompletionResponse> for Message {
+    fn from(response: &CompletionResponse) -> Self {
+        Message::new_structured(Role::Assistant, response.content.clone())
+    }
+}
+
+/// A code execution container
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Container {
+    /// ID of the container, used to reuse it in follow-up requests
+    pub id: String,
+
+    /// RFC 3339 time at which the container expires
+    pub expires_at: String,
+}
+
+impl CompletionResponse {
+    /// Service tier that served the request, if reported
+    pub fn service_tier(&self) -> Option<&str> {
+        self.usage.service_tier.as_deref()
+    }
+
+    /// Concatenate the text of all text blocks, ignoring other content
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                MessageContent::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Whether the model is asking for a tool to be called
+    ///
+    /// True when generation stopped for tool use or any content block is a tool use.
+    pub fn requires_tool_use(&self) -> bool {
+        self.stop_reason == Some(StopReason::ToolUse)
+            || self
+                .content
+                .iter()
+                .any(|block| matches!(block, MessageContent::ToolUse { .. }))
+    }
+
+    /// Whether the model finished its turn naturally
+    pub fn is_complete(&self) -> bool {
+        self.stop_reason == Some(StopReason::EndTurn)
+    }
+
+    /// Whether the model declined to respond for safety reasons
+    pub fn is_refusal(&self) -> bool {
+        self.stop_reason == Some(StopReason::Refusal)
+    }
+
+    /// Text of a refused response, or `None` if the response was not a refusal
+    ///
+    /// The text may be empty or a partial answer cut off when the refusal triggered.
+    pub fn refusal_text(&self) -> Option<String> {
+        self.is_refusal().then(|| self.text())
+    }
+
+    /// Get `(id, name, input)` for every tool use block
+    pub fn tool_uses(&self) -> Vec<(&str, &str, &serde_json::Value)> {
+        self.content
+            .iter()
+            .filter_map(|block| match block {
+                MessageContent::ToolUse {
+                    id, name, input, ..
+                } => Some((id.as_str(), name.as_str(), input)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Reason why generation stopped
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum StopReason {
     /// Generation stopped because the end of a turn was reached
     #[serde(rename = "end_turn")]
     EndTurn,
 
-    /// Generation stopped because the maximum token limit was reached
-    #[serde(rename = "max_tokens")]
-    MaxTokens,
+    /// Generation stopped because the maximum token limit was reached
+    #[serde(rename = "max_tokens")]
+    MaxTokens,
+
+    /// Generation stopped because a stop sequence was encountered
+    #[serde(rename = "stop_sequence")]
+    StopSequence,
+
+    /// Generation stopped because a tool was used
+    #[serde(rename = "tool_use")]
+    ToolUse,
+
+    /// A long-running server tool turn was paused and can be continued
+    #[serde(rename = "pause_turn")]
+    PauseTurn,
+
+    /// Claude refused to respond for safety reasons
+    #[serde(rename = "refusal")]
+    Refusal,
+
+    /// A stop reason not known to this crate
+    #[serde(rename = "unknown", other)]
+    Unknown,
+}
+
+impl StopReason {
+    /// Wire name of the stop reason
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StopReason::EndTurn => "end_turn",
+            StopReason::MaxTokens => "max_tokens",
+            StopReason::StopSequence => "stop_sequence",
+            StopReason::ToolUse => "tool_use",
+            StopReason::PauseTurn => "pause_turn",
+            StopReason::Refusal => "refusal",
+            StopReason::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Request format for the anthropic-proxy actor
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum AnthropicRequest {
+    ListModels,
+
+    GenerateCompletion { request: CompletionRequest },
+}
+
+/// Response status
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseStatus {
+    /// Operation succeeded
+    #[serde(rename = "Success")]
+    Success,
+
+    /// Operation failed
+    #[serde(rename = "Error")]
+    Error,
+}
+
+impl ResponseStatus {
+    /// Whether the operation succeeded
+    pub fn is_success(&self) -> bool {
+        matches!(self, ResponseStatus::Success)
+    }
+}
+
+/// Response format from the anthropic-proxy actor
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum AnthropicResponse {
+    /// List of available models
+    ListModels { models: Vec<ModelInfo> },
+
+    /// Generated completion
+    Completion { completion: CompletionResponse },
+
+    /// Error response
+    Error { error: String },
+}
+
+impl AnthropicResponse {
+    /// Status of the response, derived from its variant
+    pub fn status(&self) -> ResponseStatus {
+        match self {
+            AnthropicResponse::Error { .. } => ResponseStatus::Error,
+            AnthropicResponse::ListModels { .. } | AnthropicResponse::Completion { .. } => {
+                ResponseStatus::Success
+            }
+        }
+    }
+
+    /// Whether this is a successful response
+    pub fn is_success(&self) -> bool {
+        self.status().is_success()
+    }
+
+    /// Extract the completion, converting an error response into an `AnthropicError`
+    ///
+    /// The proxy does not forward the HTTP status, so errors carry status 0.
+    pub fn into_completion(self) -> Result<CompletionResponse, AnthropicError> {
+        match self {
+            AnthropicResponse::Completion { completion } => Ok(completion),
+            AnthropicResponse::Error { error } => Err(AnthropicError::ApiError {
+                status: 0,
+                message: error,
+            }),
+            AnthropicResponse::ListModels { .. } => Err(AnthropicError::InvalidResponse(
+                "expected a completion, got a model list".to_string(),
+            )),
+        }
+    }
+
+    /// Extract the model list, converting an error response into an `AnthropicError`
+    ///
+    /// The proxy does not forward the HTTP status, so errors carry status 0.
+    pub fn into_models(self) -> Result<Vec<ModelInfo>, AnthropicError> {
+        match self {
+            AnthropicResponse::ListModels { models } => Ok(models),
+            AnthropicResponse::Error { error } => Err(AnthropicError::ApiError {
+                status: 0,
+                message: error,
+            }),
+            AnthropicResponse::Completion { .. } => Err(AnthropicError::InvalidResponse(
+                "expected a model list, got a completion".to_string(),
+            )),
+        }
+    }
+}
+
+impl From<CompletionResponse> for AnthropicResponse {
+    fn from(completion: CompletionResponse) -> Self {
+        AnthropicResponse::Completion { completion }
+    }
+}
+
+impl From<Vec<ModelInfo>> for AnthropicResponse {
+    fn from(models: Vec<ModelInfo>) -> Self {
+        AnthropicResponse::ListModels { models }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(deprecated)]
+    fn test_request() -> CompletionRequest {
+        CompletionRequest {
+            model: "claude-3-7-sonnet-20250219".to_string(),
+            messages: vec![],
+            max_tokens: 1024,
+            stop_sequences: None,
+            temperature: None,
+            top_p: None,
+            top_k: None,
+            system: None,
+            tools: None,
+            tool_choice: None,
+            disable_parallel_tool_use: None,
+            thinking: None,
+            metadata: None,
+            service_tier: None,
+            container: None,
+            mcp_servers: None,
+        }
+    }
+
+    #[test]
+    fn test_deserialize_completion_request_with_system_messages() {
+        let json = r#"{
+            "model": "claude-3-7-sonnet-20250219",
+            "max_tokens": 1024,
+            "system": [
+              {
+                "type": "text",
+                "text": "You are an AI assistant tasked with analyzing literary works. Your goal is to provide insightful commentary on themes, characters, and writing style.\n"
+              },
+              {
+                "type": "text",
+                "text": "<the entire contents of Pride and Prejudice>",
+                "cache_control": {"type": "ephemeral"}
+              }
+            ],
+            "messages": [
+              {
+                "role": "user",
+                "content": "Analyze the major themes in Pride and Prejudice."
+              }
+            ]
+          }"#;
+
+        serde_json::from_str::<CompletionRequest>(json).expect("Failed to deserialize request");
+    }
+
+    #[test]
+    fn test_deserialize_completion_request_with_system_message_string() {
+        let json = r#"{
+            "model": "claude-3-7-sonnet-20250219",
+            "max_tokens": 1024,
+            "system": "You are an AI assistant tasked with analyzing literary works. Your goal is to provide insightful commentary on themes, characters, and writing style.",
+            "messages": [
+              {
+                "role": "user",
+                "content": "Analyze the major themes in Pride and Prejudice."
+              }
+            ]
+        }"#;
+
+        serde_json::from_str::<CompletionRequest>(json).expect("Failed to deserialize request");
+    }
+
+    #[test]
+    fn test_deserialize_image_base64_source() {
+        let json = r#"{
+            "type": "image",
+            "source": {
+                "type": "base64",
+                "media_type": "image/png",
+                "data": "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg=="
+            }
+        }"#;
+
+        let content: MessageContent =
+            serde_json::from_str(json).expect("Failed to deserialize image block");
+        match &content {
+            MessageContent::Image {
+                source: ImageSource::Base64 { media_type, .. },
+                cache_control: None,
+            } => assert_eq!(media_type, "image/png"),
+            other => panic!("Unexpected content: {:?}", other),
+        }
+
+        let expected: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(serde_json::to_value(&content).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_deserialize_image_url_source() {
+        let json = r#"{
+            "type": "image",
+            "source": {
+                "type": "url",
+                "url": "https://upload.wikimedia.org/wikipedia/commons/a/a7/Camponotus_flavomarginatus_ant.jpg"
+            },
+            "cache_control": {"type": "ephemeral"}
+        }"#;
+
+        let content: MessageContent =
+            serde_json::from_str(json).expect("Failed to deserialize image block");
+        match &content {
+            MessageContent::Image {
+                source: ImageSource::Url { url },
+                cache_control: Some(_),
+            } => assert!(url.ends_with(".jpg")),
+            other => panic!("Unexpected content: {:?}", other),
+        }
+
+        let expected: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(serde_json::to_value(&content).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_role_serialization() {
+        let message = Message::new_structured(Role::Assistant, vec![MessageContent::text("Hello")]);
+        assert_eq!(message.role, Role::Assistant);
+
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["role"], "assistant");
+
+        let parsed: Message = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.role, Role::Assistant);
+    }
+
+    #[test]
+    fn test_role_from_str() {
+        assert_eq!("user".parse::<Role>(), Ok(Role::User));
+        assert_eq!("system".parse::<Role>(), Ok(Role::System));
+        assert!("assisstant".parse::<Role>().is_err());
+        assert_eq!(Role::try_from("assistant"), Ok(Role::Assistant));
+        assert_eq!(
+            Role::try_from("bot".to_string()),
+            Err("Unknown role: bot".to_string())
+        );
+        assert_eq!(Role::Assistant.to_string(), "assistant");
+
+        let json = r#"{"role": "assisstant", "content": "Hi"}"#;
+        assert!(serde_json::from_str::<Message>(json).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_response_with_thinking_blocks() {
+        let json = r#"{
+            "id": "msg_01XFDUDYJgAACzvnptvVoYEL",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-3-7-sonnet-20250219",
+            "content": [
+                {
+                    "type": "thinking",
+                    "thinking": "To approach this, let's think about what we know about prime numbers...",
+                    "signature": "zbbJhbGciOiJFU8zI1NiIsImtakcjsu38219c0.eyJoYXNoIjoiYWJjMTIzIiwiaWF0IjoxNjE0NTM0NTY3fQ"
+                },
+                {
+                    "type": "redacted_thinking",
+                    "data": "EmwKAhgBEgy3va3pzix/LafPsn4aDFIT2Xlxh0L5L8rLVyIwxtE3rAFBa8cr3qpP"
+                },
+                {
+                    "type": "text",
+                    "text": "Yes, there are infinitely many prime numbers such that..."
+                }
+            ],
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {
+                "input_tokens": 48,
+                "output_tokens": 1024
+            }
+        }"#;
+
+        let response: CompletionResponse =
+            serde_json::from_str(json).expect("Failed to deserialize response");
+        assert!(matches!(
+            response.content[0],
+            MessageContent::Thinking { .. }
+        ));
+        assert!(matches!(
+            response.content[1],
+            MessageContent::RedactedThinking { .. }
+        ));
+        assert!(matches!(response.content[2], MessageContent::Text { .. }));
+
+        let round_trip: CompletionResponse =
+            serde_json::from_str(&serde_json::to_string(&response).unwrap()).unwrap();
+        match &round_trip.content[0] {
+            MessageContent::Thinking {
+                thinking,
+                signature,
+            } => {
+                assert!(thinking.starts_with("To approach this"));
+                assert!(!signature.is_empty());
+            }
+            other => panic!("Unexpected content: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_serialize_thinking_config() {
+        let request = CompletionRequest {
+            max_tokens: 16000,
+            thinking: Some(ThinkingConfig::enabled(10000)),
+            ..test_request()
+        };
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json["thinking"],
+            serde_json::json!({"type": "enabled", "budget_tokens": 10000})
+        );
+
+        let disabled = serde_json::to_value(ThinkingConfig::disabled()).unwrap();
+        assert_eq!(disabled, serde_json::json!({"type": "disabled"}));
+
+        let request = CompletionRequest {
+            thinking: None,
+            ..request
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("thinking").is_none());
+    }
+
+    #[test]
+    fn test_stop_sequences_round_trip() {
+        let request = CompletionRequest {
+            stop_sequences: Some(vec!["</answer>".to_string(), "\n\nHuman:".to_string()]),
+            ..test_request()
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json["stop_sequences"],
+            serde_json::json!(["</answer>", "\n\nHuman:"])
+        );
+
+        let json = r#"{
+            "id": "msg_013Zva2CMHLNnXjNJJKqJ2EF",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-3-7-sonnet-20250219",
+            "content": [{"type": "text", "text": "<answer>42"}],
+            "stop_reason": "stop_sequence",
+            "stop_sequence": "</answer>",
+            "usage": {"input_tokens": 25, "output_tokens": 4}
+        }"#;
+        let response: CompletionResponse =
+            serde_json::from_str(json).expect("Failed to deserialize response");
+        assert!(matches!(
+            response.stop_reason,
+            Some(StopReason::StopSequence)
+        ));
+        assert_eq!(response.stop_sequence.as_deref(), Some("</answer>"));
+    }
+
+    #[test]
+    fn test_serialize_sampling_parameters() {
+        let json = serde_json::to_value(test_request()).unwrap();
+        assert!(json.get("top_p").is_none());
+        assert!(json.get("top_k").is_none());
+
+        let request = CompletionRequest {
+            top_p: Some(0.5),
+            top_k: Some(40),
+            ..test_request()
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["top_p"], 0.5);
+        assert_eq!(json["top_k"], 40);
+    }
+
+    #[test]
+    fn test_serialize_request_metadata() {
+        let request = CompletionRequest {
+            metadata: Some(RequestMetadata {
+                user_id: Some("13803d75-b4b5-4c3e-b2a2-6f21399b021b".to_string()),
+            }),
+            ..test_request()
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json["metadata"],
+            serde_json::json!({"user_id": "13803d75-b4b5-4c3e-b2a2-6f21399b021b"})
+        );
+
+        let metadata: RequestMetadata =
+            serde_json::from_str(r#"{"user_id": "abc", "new_field": true}"#)
+                .expect("Unknown metadata keys should be ignored");
+        assert_eq!(metadata.user_id.as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn test_usage_estimated_cost() {
+        let pricing = ModelPricing {
+            input_cost_per_million_tokens: 3.00,
+            output_cost_per_million_tokens: 15.00,
+            cache_write_cost_per_million_tokens: None,
+            cache_read_cost_per_million_tokens: None,
+        };
+
+        let usage = Usage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            cache_read_input_tokens: None,
+            cache_creation_input_tokens: None,
+            service_tier: None,
+            server_tool_use: None,
+            cache_creation: None,
+        };
+        assert!((usage.estimated_cost(&pricing) - 4.50).abs() < 1e-9);
+
+        let usage = Usage {
+            input_tokens: 1_000,
+            output_tokens: 500,
+            cache_read_input_tokens: Some(100_000),
+            cache_creation_input_tokens: Some(20_000),
+            service_tier: None,
+            server_tool_use: None,
+            cache_creation: None,
+        };
+        // 0.003 input + 0.0075 output + 0.03 cache read + 0.075 cache write
+        assert!((usage.estimated_cost(&pricing) - 0.1155).abs() < 1e-9);
+
+        let usage = Usage {
+            cache_creation: Some(CacheCreation {
+                ephemeral_5m_input_tokens: 10_000,
+                ephemeral_1h_input_tokens: 10_000,
+            }),
+            ..usage
+        };
+        // The 1 hour half of the cache write costs 0.06 instead of 0.0375
+        assert!((usage.estimated_cost(&pricing) - 0.138).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_serialize_cache_control_ttl() {
+        assert_eq!(
+            serde_json::to_value(CacheControl::ephemeral()).unwrap(),
+            serde_json::json!({"type": "ephemeral"})
+        );
+        assert_eq!(
+            serde_json::to_value(CacheControl::ephemeral_with_ttl("5m")).unwrap(),
+            serde_json::json!({"type": "ephemeral", "ttl": "5m"})
+        );
+        assert_eq!(
+            serde_json::to_value(CacheControl::ephemeral_with_ttl("1h")).unwrap(),
+            serde_json::json!({"type": "ephemeral", "ttl": "1h"})
+        );
+    }
+
+    #[test]
+    fn test_builder_minimal() {
+        let request = CompletionRequest::builder()
+            .model("claude-3-7-sonnet-20250219")
+            .max_tokens(1024)
+            .message(Message::new_structured(
+                Role::User,
+                vec![MessageContent::text("Hello, Claude")],
+            ))
+            .system("You are a helpful assistant.")
+            .build()
+            .expect("Failed to build request");
+
+        assert_eq!(request.model, "claude-3-7-sonnet-20250219");
+        assert_eq!(request.max_tokens, 1024);
+        assert_eq!(request.messages.len(), 1);
+        assert!(matches!(
+            request.system,
+            Some(SystemMessageFormat::String(_))
+        ));
+        assert!(request.temperature.is_none());
+    }
+
+    #[test]
+    fn test_builder_validation() {
+        let err = CompletionRequest::builder()
+            .max_tokens(1024)
+            .build()
+            .unwrap_err();
+        assert_eq!(err, "model is required");
+
+        let err = CompletionRequest::builder()
+            .model("claude-3-7-sonnet-20250219")
+            .max_tokens(1024)
+            .build()
+            .unwrap_err();
+        assert_eq!(err, "at least one message is required");
+
+        let err = CompletionRequest::builder()
+            .model("claude-3-7-sonnet-20250219")
+            .build()
+            .unwrap_err();
+        assert_eq!(err, "max_tokens is required");
+    }
+
+    #[test]
+    fn test_text_message_constructors() {
+        assert_eq!(
+            serde_json::to_value(Message::user("Hello, Claude")).unwrap(),
+            serde_json::json!({"role": "user", "content": "Hello, Claude"})
+        );
+        assert_eq!(
+            serde_json::to_value(Message::assistant("Hi! How can I help?")).unwrap(),
+            serde_json::json!({"role": "assistant", "content": "Hi! How can I help?"})
+        );
+
+        let message =
+            Message::user_with_blocks(vec![MessageContent::text("What is in this image?")]);
+        assert_eq!(
+            serde_json::to_value(message).unwrap(),
+            serde_json::json!({
+                "role": "user",
+                "content": [{"type": "text", "text": "What is in this image?"}]
+            })
+        );
+    }
+
+    fn response_with_stop_reason(stop_reason: &str) -> String {
+        format!(
+            r#"{{
+                "id": "msg_01",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-sonnet-4-20250514",
+                "content": [],
+                "stop_reason": {},
+                "stop_sequence": null,
+                "usage": {{"input_tokens": 10, "output_tokens": 0}}
+            }}"#,
+            stop_reason
+        )
+    }
+
+    #[test]
+    fn test_deserialize_new_stop_reasons() {
+        let response: CompletionResponse =
+            serde_json::from_str(&response_with_stop_reason(r#""pause_turn""#)).unwrap();
+        assert!(matches!(response.stop_reason, Some(StopReason::PauseTurn)));
+
+        let response: CompletionResponse =
+            serde_json::from_str(&response_with_stop_reason(r#""refusal""#)).unwrap();
+        assert!(matches!(response.stop_reason, Some(StopReason::Refusal)));
+    }
+
+    #[test]
+    fn test_deserialize_null_and_unknown_stop_reason() {
+        let response: CompletionResponse =
+            serde_json::from_str(&response_with_stop_reason("null")).unwrap();
+        assert!(response.stop_reason.is_none());
+
+        let response: CompletionResponse = serde_json::from_str(&response_with_stop_reason(
+            r#""model_context_window_exceeded_v2""#,
+        ))
+        .expect("Unknown stop reasons should still parse");
+        assert!(matches!(response.stop_reason, Some(StopReason::Unknown)));
+    }
+
+    #[test]
+    fn test_count_tokens_request_from_completion_request() {
+        let request = CompletionRequest {
+            messages: vec![Message::user("Hello, world")],
+            temperature: Some(0.7),
+            system: Some(SystemMessageFormat::String("Be concise.".to_string())),
+            thinking: Some(ThinkingConfig::enabled(2048)),
+            ..test_request()
+        };
+
+        let count_request = CountTokensRequest::from(&request);
+        assert_eq!(
+            serde_json::to_value(&count_request).unwrap(),
+            serde_json::json!({
+                "model": "claude-3-7-sonnet-20250219",
+                "messages": [{"role": "user", "content": "Hello, world"}],
+                "system": "Be concise.",
+                "thinking": {"type": "enabled", "budget_tokens": 2048}
+            })
+        );
+
+        let response: CountTokensResponse =
+            serde_json::from_str(r#"{"input_tokens": 2095}"#).unwrap();
+        assert_eq!(response.input_tokens, 2095);
+    }
+
+    #[test]
+    fn test_push_agent_loop_turns() {
+        let mut request = CompletionRequest {
+            messages: vec![Message::user("What's the weather in Paris?")],
+            ..test_request()
+        };
+
+        let response: CompletionResponse = serde_json::from_str(
+            r#"{
+                "id": "msg_01",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-3-7-sonnet-20250219",
+                "content": [
+                    {"type": "text", "text": "Let me check."},
+                    {"type": "tool_use", "id": "toolu_01", "name": "get_weather", "input": {"city": "Paris"}}
+                ],
+                "stop_reason": "tool_use",
+                "stop_sequence": null,
+                "usage": {"input_tokens": 20, "output_tokens": 30}
+            }"#,
+        )
+        .unwrap();
+        request.push_assistant_response(&response);
+        request.push_tool_results(vec![MessageContent::ToolResult {
+            tool_use_id: "toolu_01".to_string(),
+            content: vec![ToolContent::Text {
+                text: "18°C and sunny".to_string(),
+            }],
+            is_error: None,
+            cache_control: None,
+        }]);
+        request.push_message(Message::user("Thanks! And in Rome?"));
+
+        let json = serde_json::to_value(&request).unwrap();
+        let messages = json["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[1]["role"], "assistant");
+        assert_eq!(messages[1]["content"][1]["type"], "tool_use");
+        assert_eq!(messages[2]["role"], "user");
+        assert_eq!(messages[2]["content"][0]["type"], "tool_result");
+        assert_eq!(messages[2]["content"][0]["tool_use_id"], "toolu_01");
+        assert_eq!(messages[3]["content"], "Thanks! And in Rome?");
+    }
+
+    #[test]
+    fn test_response_text_and_tool_uses() {
+        let response: CompletionResponse = serde_json::from_str(
+            r#"{
+                "id": "msg_01",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-3-7-sonnet-20250219",
+                "content": [
+                    {"type": "thinking", "thinking": "The user wants two lookups.", "signature": "sig"},
+                    {"type": "text", "text": "I'll look up both. "},
+                    {"type": "tool_use", "id": "toolu_01", "name": "get_weather", "input": {"city": "Paris"}},
+                    {"type": "text", "text": "One moment."},
+                    {"type": "tool_use", "id": "toolu_02", "name": "get_time", "input": {"tz": "CET"}}
+                ],
+                "stop_reason": "tool_use",
+                "stop_sequence": null,
+                "usage": {"input_tokens": 20, "output_tokens": 30}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(response.text(), "I'll look up both. One moment.");
+
+        let tool_uses = response.tool_uses();
+        assert_eq!(tool_uses.len(), 2);
+        assert_eq!(tool_uses[0].0, "toolu_01");
+        assert_eq!(tool_uses[0].1, "get_weather");
+        assert_eq!(tool_uses[0].2["city"], "Paris");
+        assert_eq!(tool_uses[1].1, "get_time");
+    }
+
+    #[test]
+    fn test_service_tier_serde() {
+        let json = serde_json::to_value(test_request()).unwrap();
+        assert!(json.get("service_tier").is_none());
+
+        let request = CompletionRequest {
+            service_tier: Some(ServiceTier::StandardOnly),
+            ..test_request()
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["service_tier"], "standard_only");
+        assert_eq!(
+            serde_json::to_value(ServiceTier::Auto).unwrap(),
+            serde_json::json!("auto")
+        );
+
+        let response: CompletionResponse = serde_json::from_str(
+            r#"{
+                "id": "msg_01",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-sonnet-4-20250514",
+                "content": [],
+                "stop_reason": "end_turn",
+                "stop_sequence": null,
+                "usage": {"input_tokens": 10, "output_tokens": 5, "service_tier": "priority"}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(response.service_tier(), Some("priority"));
+    }
+
+    #[test]
+    fn test_deserialize_cited_response() {
+        let json = r#"{
+            "id": "msg_01",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-3-7-sonnet-20250219",
+            "content": [
+                {"type": "text", "text": "According to the document, "},
+                {
+                    "type": "text",
+                    "text": "the grass is green",
+                    "citations": [{
+                        "type": "char_location",
+                        "cited_text": "The grass is green.",
+                        "document_index": 0,
+                        "document_title": "Example Document",
+                        "start_char_index": 0,
+                        "end_char_index": 20
+                    }]
+                },
+                {
+                    "type": "text",
+                    "text": " and the sky is blue",
+                    "citations": [{
+                        "type": "page_location",
+                        "cited_text": "The sky is blue.",
+                        "document_index": 1,
+                        "document_title": null,
+                        "start_page_number": 2,
+                        "end_page_number": 3
+                    }, {
+                        "type": "content_block_location",
+                        "cited_text": "Skies are blue.",
+                        "document_index": 2,
+                        "document_title": "Notes",
+                        "start_block_index": 0,
+                        "end_block_index": 1
+                    }]
+                }
+            ],
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {"input_tokens": 100, "output_tokens": 20}
+        }"#;
+
+        let response: CompletionResponse =
+            serde_json::from_str(json).expect("Failed to deserialize cited response");
+        assert!(matches!(
+            response.content[0],
+            MessageContent::Text {
+                citations: None,
+                ..
+            }
+        ));
+        match &response.content[1] {
+            MessageContent::Text {
+                citations: Some(citations),
+                ..
+            } => match &citations[0] {
+                Citation::CharLocation {
+                    cited_text,
+                    end_char_index,
+                    ..
+                } => {
+                    assert_eq!(cited_text, "The grass is green.");
+                    assert_eq!(*end_char_index, 20);
+                }
+                other => panic!("Unexpected citation: {:?}", other),
+            },
+            other => panic!("Unexpected content: {:?}", other),
+        }
+        match &response.content[2] {
+            MessageContent::Text {
+                citations: Some(citations),
+                ..
+            } => {
+                assert!(matches!(citations[0], Citation::PageLocation { .. }));
+                assert!(matches!(
+                    citations[1],
+                    Citation::ContentBlockLocation { .. }
+                ));
+            }
+            other => panic!("Unexpected content: {:?}", other),
+        }
+
+        let expected: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            serde_json::to_value(&response).unwrap()["content"],
+            expected["content"]
+        );
+    }
+
+    #[test]
+    fn test_serialize_text_block_cache_breakpoint() {
+        let message = Message::user_with_blocks(vec![
+            MessageContent::text("Here is the full contract:"),
+            MessageContent::Text {
+                text: "<contract text>".to_string(),
+                citations: None,
+                cache_control: Some(CacheControl::ephemeral()),
+            },
+        ]);
+
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!({
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "Here is the full contract:"},
+                    {
+                        "type": "text",
+                        "text": "<contract text>",
+                        "cache_control": {"type": "ephemeral"}
+                    }
+                ]
+            })
+        );
+
+        let result = MessageContent::ToolResult {
+            tool_use_id: "toolu_01".to_string(),
+            content: vec![],
+            is_error: None,
+            cache_control: Some(CacheControl::ephemeral()),
+        };
+        assert_eq!(
+            serde_json::to_value(&result).unwrap()["cache_control"],
+            serde_json::json!({"type": "ephemeral"})
+        );
+    }
+
+    #[test]
+    fn test_container_round_trip() {
+        let json = serde_json::to_value(test_request()).unwrap();
+        assert!(json.get("container").is_none());
+
+        let request = CompletionRequest {
+            container: Some("container_011CPR5CNjB747bTd36fQLFk".to_string()),
+            ..test_request()
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["container"], "container_011CPR5CNjB747bTd36fQLFk");
+        let parsed: CompletionRequest = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            parsed.container.as_deref(),
+            Some("container_011CPR5CNjB747bTd36fQLFk")
+        );
+
+        let json = serde_json::json!({
+            "id": "msg_01",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-sonnet-4-20250514",
+            "content": [],
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {
+                "input_tokens": 10,
+                "output_tokens": 5,
+                "cache_read_input_tokens": null,
+                "cache_creation_input_tokens": null
+            },
+            "container": {
+                "id": "container_011CPR5CNjB747bTd36fQLFk",
+                "expires_at": "2025-05-23T21:13:31.749448Z"
+            }
+        });
+        let response: CompletionResponse = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(
+            response.container.as_ref().map(|c| c.id.as_str()),
+            Some("container_011CPR5CNjB747bTd36fQLFk")
+        );
+        assert_eq!(serde_json::to_value(&response).unwrap(), json);
+
+        let response: CompletionResponse =
+            serde_json::from_str(&response_with_stop_reason(r#""end_turn""#)).unwrap();
+        assert!(response.container.is_none());
+        assert!(serde_json::to_value(&response)
+            .unwrap()
+            .get("container")
+            .is_none());
+    }
+
+    #[test]
+    fn test_deserialize_code_execution_results() {
+        let success: MessageContent = serde_json::from_str(
+            r#"{
+                "type": "code_execution_tool_result",
+                "tool_use_id": "srvtoolu_01A2B3C4D5E6F7G8H9I0J1K2",
+                "content": {
+                    "type": "code_execution_result",
+                    "stdout": "Mean: 5.5\nStd: 2.87\n",
+                    "stderr": "",
+                    "return_code": 0,
+                    "content": [
+                        {"type": "code_execution_output", "file_id": "file_011CPR5CNjB747bTd36fQLFk"}
+                    ]
+                }
+            }"#,
+        )
+        .expect("Failed to deserialize code execution result");
+        match success {
+            MessageContent::CodeExecutionToolResult {
+                content:
+                    CodeExecutionResult::Result {
+                        stdout,
+                        return_code,
+                        content,
+                        ..
+                    },
+                ..
+            } => {
+                assert!(stdout.starts_with("Mean: 5.5"));
+                assert_eq!(return_code, 0);
+                assert_eq!(content[0].file_id, "file_011CPR5CNjB747bTd36fQLFk");
+            }
+            other => panic!("Unexpected content: {:?}", other),
+        }
+
+        let failure: MessageContent = serde_json::from_str(
+            r#"{
+                "type": "code_execution_tool_result",
+                "tool_use_id": "srvtoolu_02",
+                "content": {
+                    "type": "code_execution_result",
+                    "stdout": "",
+                    "stderr": "NameError: name 'undefined_var' is not defined",
+                    "return_code": 1
+                }
+            }"#,
+        )
+        .expect("Failed to deserialize code execution result");
+        match failure {
+            MessageContent::CodeExecutionToolResult {
+                content:
+                    CodeExecutionResult::Result {
+                        stderr,
+                        return_code,
+                        content,
+                        ..
+                    },
+                ..
+            } => {
+                assert!(stderr.contains("NameError"));
+                assert_eq!(return_code, 1);
+                assert!(content.is_empty());
+            }
+            other => panic!("Unexpected content: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_validate_sampling_ranges() {
+        let mut request = test_request();
+        assert!(request.set_temperature(0.0).is_ok());
+        assert!(request.set_temperature(1.0).is_ok());
+        assert_eq!(request.temperature, Some(1.0));
+
+        assert_eq!(
+            request.set_temperature(2.5),
+            Err("temperature must be between 0.0 and 1.0, got 2.5".to_string())
+        );
+        assert!(request.set_temperature(-0.1).is_err());
+        assert!(request.set_temperature(f32::NAN).is_err());
+        assert_eq!(request.temperature, Some(1.0));
+
+        assert!(request.set_top_p(0.9).is_ok());
+        assert!(request.set_top_p(1.5).is_err());
+        assert_eq!(request.top_p, Some(0.9));
+
+        let err = CompletionRequest::builder()
+            .model("claude-3-7-sonnet-20250219")
+            .max_tokens(1024)
+            .message(Message::user("Hi"))
+            .temperature(2.5)
+            .build()
+            .unwrap_err();
+        assert!(err.starts_with("temperature must be between"));
+    }
+
+    #[test]
+    fn test_response_equality() {
+        let build = || CompletionResponse {
+            content: vec![
+                MessageContent::text("Here you go"),
+                MessageContent::ToolResult {
+                    tool_use_id: "toolu_01".to_string(),
+                    content: vec![ToolContent::Text {
+                        text: "42".to_string(),
+                    }],
+                    is_error: None,
+                    cache_control: None,
+                },
+            ],
+            id: "msg_01".to_string(),
+            model: "claude-3-7-sonnet-20250219".to_string(),
+            role: Role::Assistant,
+            stop_reason: Some(StopReason::EndTurn),
+            stop_sequence: None,
+            message_type: "message".to_string(),
+            usage: Usage {
+                input_tokens: 10,
+                output_tokens: 5,
+                cache_read_input_tokens: None,
+                cache_creation_input_tokens: None,
+                service_tier: None,
+                server_tool_use: None,
+                cache_creation: None,
+            },
+            container: None,
+        };
+
+        assert_eq!(build(), build());
+
+        let mut different = build();
+        different.content[1] = MessageContent::ToolResult {
+            tool_use_id: "toolu_01".to_string(),
+            content: vec![ToolContent::Text {
+                text: "43".to_string(),
+            }],
+            is_error: None,
+            cache_control: None,
+        };
+        assert_ne!(build(), different);
+    }
+
+    const RESPONSE_WITH_UNKNOWN_FIELDS: &str = r#"{
+        "id": "msg_01",
+        "type": "message",
+        "role": "assistant",
+        "model": "claude-3-7-sonnet-20250219",
+        "content": [],
+        "stop_reason": "end_turn",
+        "stop_sequence": null,
+        "usage": {"input_tokens": 10, "output_tokens": 5, "future_counter": 1},
+        "future_field": true
+    }"#;
+
+    #[test]
+    #[cfg(not(feature = "strict"))]
+    fn test_lenient_ignores_unknown_fields() {
+        serde_json::from_str::<CompletionResponse>(RESPONSE_WITH_UNKNOWN_FIELDS)
+            .expect("Unknown fields should be ignored");
+    }
+
+    #[test]
+    #[cfg(feature = "strict")]
+    fn test_strict_rejects_unknown_fields() {
+        let err =
+            serde_json::from_str::<CompletionResponse>(RESPONSE_WITH_UNKNOWN_FIELDS).unwrap_err();
+        assert!(err.to_string().contains("unknown field"));
+
+        let err =
+            serde_json::from_str::<Usage>(r#"{"input_tokens": 1, "output_tokens": 1, "extra": 0}"#)
+                .unwrap_err();
+        assert!(err.to_string().contains("unknown field `extra`"));
+    }
+
+    #[test]
+    fn test_usage_totals() {
+        let mut usage = Usage {
+            input_tokens: 100,
+            output_tokens: 50,
+            cache_read_input_tokens: None,
+            cache_creation_input_tokens: None,
+            service_tier: None,
+            server_tool_use: None,
+            cache_creation: None,
+        };
+        assert_eq!(usage.total_input_tokens(), 100);
+        assert_eq!(usage.total_tokens(), 150);
+
+        usage.cache_read_input_tokens = Some(1000);
+        usage.cache_creation_input_tokens = Some(200);
+        assert_eq!(usage.total_input_tokens(), 1300);
+        assert_eq!(usage.total_tokens(), 1350);
+
+        usage.cache_read_input_tokens = Some(u32::MAX);
+        assert_eq!(usage.total_tokens(), u32::MAX);
+    }
+
+    #[test]
+    fn test_usage_addition() {
+        let a = Usage {
+            input_tokens: 100,
+            output_tokens: 10,
+            cache_read_input_tokens: Some(500),
+            cache_creation_input_tokens: None,
+            service_tier: None,
+            server_tool_use: None,
+            cache_creation: None,
+        };
+        let b = Usage {
+            input_tokens: 50,
+            output_tokens: 20,
+            cache_read_input_tokens: None,
+            cache_creation_input_tokens: None,
+            service_tier: None,
+            server_tool_use: None,
+            cache_creation: None,
+        };
+
+        let total = a.clone() + b.clone();
+        assert_eq!(total.input_tokens, 150);
+        assert_eq!(total.output_tokens, 30);
+        assert_eq!(total.cache_read_input_tokens, Some(500));
+        assert_eq!(total.cache_creation_input_tokens, None);
+
+        let mut running = Usage::default();
+        running += b.clone();
+        assert_eq!(running, b);
+
+        let summed: Usage = vec![a.clone(), b.clone(), a].into_iter().sum();
+        assert_eq!(summed.input_tokens, 250);
+        assert_eq!(summed.cache_read_input_tokens, Some(1000));
+        assert_eq!(summed.cache_creation_input_tokens, None);
+    }
+
+    #[test]
+    fn test_validate_max_tokens() {
+        let request = CompletionRequest {
+            model: "claude-3-5-sonnet-20241022".to_string(),
+            max_tokens: 8192,
+            ..test_request()
+        };
+        assert!(request.validate_max_tokens().is_ok());
+
+        let request = CompletionRequest {
+            max_tokens: 64000,
+            ..request
+        };
+        assert_eq!(
+            request.validate_max_tokens(),
+            Err("max_tokens 64000 exceeds the claude-3-5-sonnet-20241022 output token limit of 8192".to_string())
+        );
+
+        // Unknown models have no limit to check against
+        let request = CompletionRequest {
+            model: "claude-future-model".to_string(),
+            ..request
+        };
+        assert!(request.validate_max_tokens().is_ok());
+    }
+
+    #[test]
+    fn test_validate_cache_breakpoints() {
+        let cached_text = |text: &str| MessageContent::Text {
+            text: text.to_string(),
+            citations: None,
+            cache_control: Some(CacheControl::ephemeral()),
+        };
+        let mut request = CompletionRequest {
+            tools: Some(vec![AnthropicTool::Custom(crate::tools::CustomTool {
+                name: "lookup".to_string(),
+                description: None,
+                input_schema: serde_json::json!({"type": "object"}),
+                cache_control: Some(CacheControl::ephemeral()),
+            })]),
+            system: Some(SystemMessageFormat::Array(vec![SystemMessage {
+                message_type: "text".to_string(),
+                text: "Long instructions".to_string(),
+                cache_control: Some(CacheControl::ephemeral()),
+            }])),
+            messages: vec![
+                Message::user_with_blocks(vec![
+                    cached_text("Document one"),
+                    MessageContent::text("Not cached"),
+                ]),
+                Message::assistant("Noted."),
+                Message::user_with_blocks(vec![cached_text("Document two")]),
+            ],
+            ..test_request()
+        };
+        assert_eq!(request.count_cache_breakpoints(), 4);
+        assert!(request.validate_cache_breakpoints().is_ok());
+
+        request.push_message(Message::user_with_blocks(vec![cached_text(
+            "Document three",
+        )]));
+        assert_eq!(request.count_cache_breakpoints(), 5);
+        assert_eq!(
+            request.validate_cache_breakpoints(),
+            Err("request has 5 cache breakpoints, at most 4 are allowed".to_string())
+        );
+    }
+
+    #[test]
+    fn test_document_block_serde() {
+        let json = serde_json::json!({
+            "type": "document",
+            "source": {
+                "type": "base64",
+                "media_type": "application/pdf",
+                "data": "JVBERi0xLjQ="
+            },
+            "title": "Report",
+            "citations": {"enabled": true},
+            "cache_control": {"type": "ephemeral"}
+        });
+        let content: MessageContent = serde_json::from_value(json.clone()).unwrap();
+        match &content {
+            MessageContent::Document {
+                source: DocumentSource::Base64 { media_type, .. },
+                title: Some(title),
+                context: None,
+                citations: Some(DocumentCitations { enabled: true }),
+                cache_control: Some(_),
+            } => {
+                assert_eq!(*media_type, MediaType::Pdf);
+                assert_eq!(title, "Report");
+            }
+            other => panic!("Unexpected content: {:?}", other),
+        }
+        assert_eq!(serde_json::to_value(&content).unwrap(), json);
+
+        let text = MessageContent::Document {
+            source: DocumentSource::text("The grass is green."),
+            title: None,
+            context: None,
+            citations: None,
+            cache_control: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&text).unwrap(),
+            serde_json::json!({
+                "type": "document",
+                "source": {"type": "text", "media_type": "text/plain", "data": "The grass is green."}
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_document_size() {
+        let document = |source: DocumentSource| MessageContent::Document {
+            source,
+            title: None,
+            context: None,
+            citations: None,
+            cache_control: None,
+        };
+
+        let small = DocumentSource::pdf(b"%PDF-1.4");
+        assert_eq!(small.estimated_size_bytes(), Some(8));
+        assert!(document(small).validate_document().is_ok());
+
+        let oversized = DocumentSource::Base64 {
+            media_type: MediaType::Pdf,
+            data: "A".repeat(44 * 1024 * 1024),
+        };
+        assert_eq!(oversized.estimated_size_bytes(), Some(33 * 1024 * 1024));
+        assert_eq!(
+            document(oversized).validate_document(),
+            Err("document is 34603008 bytes, the maximum is 33554432 bytes".to_string())
+        );
+
+        let url = DocumentSource::Url {
+            url: "https://example.com/report.pdf".to_string(),
+        };
+        assert_eq!(url.estimated_size_bytes(), None);
+        assert!(document(url).validate_document().is_ok());
+        assert!(MessageContent::text("hi").validate_document().is_ok());
+    }
+
+    #[test]
+    fn test_deserialize_file_sources() {
+        let json = serde_json::json!({
+            "type": "image",
+            "source": {"type": "file", "file_id": "file_011CNha8iCJcU1wXNR6q4V8w"}
+        });
+
+        let content: MessageContent = serde_json::from_value(json.clone()).unwrap();
+        match &content {
+            MessageContent::Image {
+                source: ImageSource::File { file_id },
+                ..
+            } => assert_eq!(file_id, "file_011CNha8iCJcU1wXNR6q4V8w"),
+            other => panic!("Unexpected content: {:?}", other),
+        }
+        assert_eq!(serde_json::to_value(&content).unwrap(), json);
+
+        let json = serde_json::json!({
+            "type": "document",
+            "source": {"type": "file", "file_id": "file_011CNha8iCJcU1wXNR6q4V8w"}
+        });
+
+        let content: MessageContent = serde_json::from_value(json.clone()).unwrap();
+        match &content {
+            MessageContent::Document {
+                source: DocumentSource::File { file_id },
+                ..
+            } => assert_eq!(file_id, "file_011CNha8iCJcU1wXNR6q4V8w"),
+            other => panic!("Unexpected content: {:?}", other),
+        }
+        assert_eq!(serde_json::to_value(&content).unwrap(), json);
+    }
+
+    #[test]
+    fn test_serialize_mcp_servers() {
+        let json = serde_json::to_value(test_request()).unwrap();
+        assert!(json.get("mcp_servers").is_none());
+
+        let request = CompletionRequest {
+            mcp_servers: Some(vec![McpServerConfig::url(
+                "example-mcp",
+                "https://example-server.modelcontextprotocol.io/sse",
+            )]),
+            ..test_request()
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            json["mcp_servers"],
+            serde_json::json!([{
+                "type": "url",
+                "url": "https://example-server.modelcontextprotocol.io/sse",
+                "name": "example-mcp"
+            }])
+        );
+    }
+
+    #[test]
+    fn test_deserialize_mcp_tool_interaction() {
+        let json = serde_json::json!({
+            "id": "msg_01",
+            "type": "message",
+            "role": "assistant",
+            "model": "claude-sonnet-4-20250514",
+            "content": [
+                {"type": "text", "text": "I'll look that up."},
+                {
+                    "type": "mcp_tool_use",
+                    "id": "mcptoolu_014Q35RayjACSWkSj4X2yov1",
+                    "name": "echo",
+                    "server_name": "example-mcp",
+                    "input": {"param1": "value1", "param2": "value2"}
+                },
+                {
+                    "type": "mcp_tool_result",
+                    "tool_use_id": "mcptoolu_014Q35RayjACSWkSj4X2yov1",
+                    "is_error": false,
+                    "content": [{"type": "text", "text": "Hello"}]
+                }
+            ],
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": {
+                "input_tokens": 100,
+                "output_tokens": 50,
+                "cache_read_input_tokens": null,
+                "cache_creation_input_tokens": null
+            }
+        });
+
+        let response: CompletionResponse = serde_json::from_value(json.clone()).unwrap();
+        match &response.content[1] {
+            MessageContent::McpToolUse {
+                name, server_name, ..
+            } => {
+                assert_eq!(name, "echo");
+                assert_eq!(server_name, "example-mcp");
+            }
+            other => panic!("Unexpected content: {:?}", other),
+        }
+        match &response.content[2] {
+            MessageContent::McpToolResult {
+                is_error, content, ..
+            } => {
+                assert_eq!(*is_error, Some(false));
+                assert_eq!(content.len(), 1);
+            }
+            other => panic!("Unexpected content: {:?}", other),
+        }
+        assert_eq!(serde_json::to_value(&response).unwrap(), json);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_legacy_disable_parallel_tool_use_still_deserializes() {
+        let json = r#"{
+            "model": "claude-3-7-sonnet-20250219",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "max_tokens": 100,
+            "tool_choice": {"type": "any"},
+            "disable_parallel_tool_use": true
+        }"#;
+        let request: CompletionRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(request.disable_parallel_tool_use, None);
+        assert_eq!(
+            request.tool_choice,
+            Some(ToolChoice::any().with_disable_parallel_tool_use(true))
+        );
+        let value = serde_json::to_value(&request).unwrap();
+        assert!(value.get("disable_parallel_tool_use").is_none());
+        assert_eq!(
+            value["tool_choice"],
+            serde_json::json!({"type": "any", "disable_parallel_tool_use": true})
+        );
+
+        // A flag already on the choice is kept
+        let json = r#"{
+            "model": "claude-3-7-sonnet-20250219",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "max_tokens": 100,
+            "tool_choice": {"type": "auto", "disable_parallel_tool_use": false},
+            "disable_parallel_tool_use": true
+        }"#;
+        let request: CompletionRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            request.tool_choice,
+            Some(ToolChoice::auto().with_disable_parallel_tool_use(false))
+        );
+
+        // Without a choice, tools imply the API's default of auto
+        let json = r#"{
+            "model": "claude-3-7-sonnet-20250219",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "max_tokens": 100,
+            "tools": [{"name": "get_weather", "input_schema": {"type": "object"}}],
+            "disable_parallel_tool_use": true
+        }"#;
+        let request: CompletionRequest = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            request.tool_choice,
+            Some(ToolChoice::auto().with_disable_parallel_tool_use(true))
+        );
+
+        let request = CompletionRequest::builder()
+            .model("claude-3-7-sonnet-20250219")
+            .max_tokens(100)
+            .message(Message::user("Hi"))
+            .tool_choice(ToolChoice::auto().with_disable_parallel_tool_use(true))
+            .build()
+            .unwrap();
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            value["tool_choice"],
+            serde_json::json!({"type": "auto", "disable_parallel_tool_use": true})
+        );
+        assert!(value.get("disable_parallel_tool_use").is_none());
+    }
+
+    #[test]
+    fn test_parse_tool_input() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct WeatherParams {
+            location: String,
+            unit: Option<String>,
+        }
+
+        let block = MessageContent::ToolUse {
+            id: "toolu_01".to_string(),
+            name: "get_weather".to_string(),
+            input: serde_json::json!({"location": "Paris"}),
+            cache_control: None,
+        };
+        let params: WeatherParams = block.parse_tool_input().unwrap();
+        assert_eq!(
+            params,
+            WeatherParams {
+                location: "Paris".to_string(),
+                unit: None,
+            }
+        );
+
+        let wrong_shape = MessageContent::ToolUse {
+            id: "toolu_02".to_string(),
+            name: "get_weather".to_string(),
+            input: serde_json::json!({"city": "Paris"}),
+            cache_control: None,
+        };
+        assert!(matches!(
+            wrong_shape.parse_tool_input::<WeatherParams>(),
+            Err(AnthropicError::JsonError(_))
+        ));
+
+        assert!(matches!(
+            MessageContent::text("hi").parse_tool_input::<WeatherParams>(),
+            Err(AnthropicError::InvalidResponse(_))
+        ));
+    }
+
+    #[test]
+    fn test_tool_result_constructors() {
+        assert_eq!(
+            serde_json::to_value(MessageContent::tool_result("toolu_01", "15 degrees")).unwrap(),
+            serde_json::json!({
+                "type": "tool_result",
+                "tool_use_id": "toolu_01",
+                "content": [{"type": "text", "text": "15 degrees"}]
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(MessageContent::tool_error("toolu_02", "city not found")).unwrap(),
+            serde_json::json!({
+                "type": "tool_result",
+                "tool_use_id": "toolu_02",
+                "content": [{"type": "text", "text": "city not found"}],
+                "is_error": true
+            })
+        );
+    }
 
-    /// Generation stopped because a stop sequence was encountered
-    #[serde(rename = "stop_sequence")]
-    StopSequence,
+    #[test]
+    fn test_to_canonical_json_is_order_independent() {
+        let mut first_schema = serde_json::Map::new();
+        first_schema.insert("type".to_string(), serde_json::json!("object"));
+        first_schema.insert("required".to_string(), serde_json::json!(["location"]));
+        first_schema.insert(
+            "properties".to_string(),
+            serde_json::json!({"location": {"type": "string"}}),
+        );
+        let mut second_schema = serde_json::Map::new();
+        second_schema.insert(
+            "properties".to_string(),
+            serde_json::json!({"location": {"type": "string"}}),
+        );
+        second_schema.insert("required".to_string(), serde_json::json!(["location"]));
+        second_schema.insert("type".to_string(), serde_json::json!("object"));
 
-    /// Generation stopped because a tool was used
-    #[serde(rename = "tool_use")]
-    ToolUse,
-}
+        let first = CompletionRequest::builder()
+            .model("claude-3-7-sonnet-20250219")
+            .max_tokens(1024)
+            .system("Be brief")
+            .message(Message::user("Weather in Paris?"))
+            .tools(vec![AnthropicTool::custom(
+                "get_weather",
+                "Get the weather",
+                first_schema.into(),
+            )])
+            .build()
+            .unwrap();
+        let second = CompletionRequest::builder()
+            .tools(vec![AnthropicTool::custom(
+                "get_weather",
+                "Get the weather",
+                second_schema.into(),
+            )])
+            .message(Message::user("Weather in Paris?"))
+            .system("Be brief")
+            .max_tokens(1024)
+            .model("claude-3-7-sonnet-20250219")
+            .build()
+            .unwrap();
 
-/// Request format for the anthropic-proxy actor
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum AnthropicRequest {
-    ListModels,
+        let canonical = first.to_canonical_json().unwrap();
+        assert_eq!(canonical, second.to_canonical_json().unwrap());
+        assert!(canonical.starts_with(r#"{"max_tokens":1024,"messages":"#));
+        assert!(canonical.contains(
+            r#""input_schema":{"properties":{"location":{"type":"string"}},"required":["location"],"type":"object"}"#
+        ));
+    }
 
-    GenerateCompletion { request: CompletionRequest },
-}
+    #[test]
+    #[cfg(feature = "sha2")]
+    fn test_cache_key() {
+        let request = CompletionRequest::builder()
+            .model("claude-3-7-sonnet-20250219")
+            .max_tokens(1024)
+            .message(Message::user("Hello"))
+            .temperature(0.5)
+            .build()
+            .unwrap();
+        let key = request.cache_key();
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, request.clone().cache_key());
 
-/// Response status
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum ResponseStatus {
-    /// Operation succeeded
-    #[serde(rename = "Success")]
-    Success,
+        let mut tweaked = request.clone();
+        tweaked.set_temperature(0.7).unwrap();
+        assert_ne!(key, tweaked.cache_key());
+    }
 
-    /// Operation failed
-    #[serde(rename = "Error")]
-    Error,
-}
+    #[test]
+    fn test_approx_char_count() {
+        let tool_turn = Message::new_structured(
+            Role::Assistant,
+            vec![
+                MessageContent::text("Checking"),
+                MessageContent::ToolUse {
+                    id: "toolu_01".to_string(),
+                    name: "get_weather".to_string(),
+                    input: serde_json::json!({"city": "Paris"}),
+                    cache_control: None,
+                },
+            ],
+        );
+        assert_eq!(Message::user("héllo").approx_char_count(), 5);
+        assert_eq!(
+            tool_turn.approx_char_count(),
+            "Checking".len() + "get_weather".len() + r#"{"city":"Paris"}"#.len()
+        );
+        assert_eq!(
+            Message::user_with_blocks(vec![MessageContent::tool_result("toolu_01", "Sunny")])
+                .approx_char_count(),
+            5
+        );
 
-/// Response format from the anthropic-proxy actor
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum AnthropicResponse {
-    /// List of available models
-    ListModels { models: Vec<ModelInfo> },
+        let mut request = test_request();
+        request.system = Some(SystemMessageFormat::String("Be brief".to_string()));
+        request.messages = vec![Message::user("Hi"), tool_turn];
+        assert_eq!(request.approx_char_count(), 8 + 2 + 35);
+    }
 
-    /// Generated completion
-    Completion { completion: CompletionResponse },
+    #[test]
+    fn test_truncate_history_keeps_recent_turns() {
+        let mut request = test_request();
+        request.messages = vec![
+            Message::user("task"),
+            Message::assistant("a1"),
+            Message::user("u2"),
+            Message::assistant("a2"),
+            Message::user("u3"),
+            Message::assistant("a3"),
+            Message::user("u4"),
+        ];
 
-    /// Error response
-    Error { error: String },
-}
+        let mut five = request.clone();
+        five.truncate_history(5);
+        let texts: Vec<_> = five
+            .messages
+            .iter()
+            .map(|message| match &message.content {
+                MessageContentFormat::String(text) => text.as_str(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(texts, ["task", "a2", "u3", "a3", "u4"]);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // A tail that would start on a user turn is shortened to keep alternation
+        let mut four = request.clone();
+        four.truncate_history(4);
+        assert_eq!(four.messages.len(), 3);
+        assert_eq!(four.messages[0], Message::user("task"));
+        assert_eq!(four.messages[1], Message::assistant("a3"));
+
+        let mut unchanged = request.clone();
+        unchanged.truncate_history(10);
+        assert_eq!(unchanged, request);
+
+        let mut zero = request.clone();
+        zero.truncate_history(0);
+        assert_eq!(zero.messages, vec![Message::user("task")]);
+
+        request.truncate_history(1);
+        assert_eq!(request.messages, vec![Message::user("task")]);
+    }
 
     #[test]
-    fn test_deserialize_completion_request_with_system_messages() {
-        let json = r#"{
-            "model": "claude-3-7-sonnet-20250219",
-            "max_tokens": 1024,
-            "system": [
-              {
-                "type": "text",
-                "text": "You are an AI assistant tasked with analyzing literary works. Your goal is to provide insightful commentary on themes, characters, and writing style.\n"
-              },
-              {
+    fn test_message_content_blocks_and_text() {
+        let plain = Message::user("Hello, world");
+        let structured = Message::user_with_blocks(vec![
+            MessageContent::text("Hello, "),
+            MessageContent::Image {
+                source: ImageSource::Url {
+                    url: "https://example.com/cat.png".to_string(),
+                },
+                cache_control: None,
+            },
+            MessageContent::text("world"),
+        ]);
+
+        assert_eq!(plain.text(), "Hello, world");
+        assert_eq!(structured.text(), plain.text());
+        assert_eq!(
+            plain.content_blocks(),
+            vec![MessageContent::text("Hello, world")]
+        );
+        assert_eq!(structured.content_blocks().len(), 3);
+    }
+
+    #[test]
+    fn test_normalize_content() {
+        assert_eq!(
+            Message::user("Hi").into_structured(),
+            Message::user_with_blocks(vec![MessageContent::text("Hi")])
+        );
+
+        let structured = Message::new_structured(
+            Role::Assistant,
+            vec![MessageContent::tool_result("t", "ok")],
+        );
+        assert_eq!(structured.clone().into_structured(), structured);
+
+        let mut request = test_request();
+        request.messages = vec![Message::user("Hi"), Message::assistant("Hello")];
+        request.normalize_content();
+        assert_eq!(
+            request.messages,
+            vec![
+                Message::user_with_blocks(vec![MessageContent::text("Hi")]),
+                Message::new_structured(Role::Assistant, vec![MessageContent::text("Hello")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_system_push_text_to_string() {
+        let mut system = SystemMessageFormat::String("You are helpful. ".to_string());
+        system.push_text("Context follows.", Some(CacheControl::ephemeral()));
+
+        assert_eq!(
+            serde_json::to_value(&system).unwrap(),
+            serde_json::json!([
+                {"type": "text", "text": "You are helpful. "},
+                {"type": "text", "text": "Context follows.", "cache_control": {"type": "ephemeral"}}
+            ])
+        );
+        assert_eq!(system.as_text(), "You are helpful. Context follows.");
+    }
+
+    #[test]
+    fn test_system_push_text_to_array() {
+        let mut system = SystemMessageFormat::Array(vec![SystemMessage {
+            message_type: "text".to_string(),
+            text: "First. ".to_string(),
+            cache_control: None,
+        }]);
+        system.push_text("Second.", None);
+
+        match &system {
+            SystemMessageFormat::Array(blocks) => {
+                assert_eq!(blocks.len(), 2);
+                assert_eq!(blocks[1].text, "Second.");
+                assert!(blocks[1].cache_control.is_none());
+            }
+            SystemMessageFormat::String(_) => panic!("expected array"),
+        }
+        assert_eq!(system.as_text(), "First. Second.");
+    }
+
+    #[test]
+    fn test_system_message_constructors() {
+        assert_eq!(
+            serde_json::to_value(SystemMessage::text("Be brief")).unwrap(),
+            serde_json::json!({"type": "text", "text": "Be brief"})
+        );
+        assert_eq!(
+            serde_json::to_value(SystemMessage::cached("Long context")).unwrap(),
+            serde_json::json!({
                 "type": "text",
-                "text": "<the entire contents of Pride and Prejudice>",
+                "text": "Long context",
                 "cache_control": {"type": "ephemeral"}
-              }
+            })
+        );
+
+        assert_eq!(
+            SystemMessageFormat::from("Be brief".to_string()),
+            SystemMessageFormat::String("Be brief".to_string())
+        );
+
+        // The builder also accepts a borrowed String
+        let prompt = "Be brief".to_string();
+        let request = CompletionRequest::builder()
+            .model("claude-3-7-sonnet-20250219")
+            .max_tokens(100)
+            .message(Message::user("Hi"))
+            .system(&prompt)
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.system,
+            Some(SystemMessageFormat::String(prompt.clone()))
+        );
+        let request = CompletionRequest::builder()
+            .model("claude-3-7-sonnet-20250219")
+            .max_tokens(100)
+            .message(Message::user("Hi"))
+            .system(vec![
+                SystemMessage::text("Be brief"),
+                SystemMessage::cached("Long context"),
+            ])
+            .build()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["system"],
+            serde_json::json!([
+                {"type": "text", "text": "Be brief"},
+                {"type": "text", "text": "Long context", "cache_control": {"type": "ephemeral"}}
+            ])
+        );
+    }
+
+    #[test]
+    fn test_message_content_format_conversions() {
+        assert_eq!(
+            MessageContentFormat::from("hi"),
+            MessageContentFormat::String("hi".to_string())
+        );
+        assert_eq!(
+            MessageContentFormat::from("hi".to_string()),
+            MessageContentFormat::String("hi".to_string())
+        );
+        assert_eq!(
+            MessageContentFormat::from(&"hi".to_string()),
+            MessageContentFormat::String("hi".to_string())
+        );
+        assert_eq!(
+            MessageContentFormat::from(vec![MessageContent::text("hi")]),
+            MessageContentFormat::Structured(vec![MessageContent::text("hi")])
+        );
+
+        let message = Message {
+            role: Role::User,
+            content: "hi".into(),
+        };
+        assert_eq!(message, Message::user("hi"));
+    }
+
+    #[test]
+    fn test_content_format_error_messages() {
+        let err = serde_json::from_str::<Message>(r#"{"role": "user", "content": 42}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("expected string or array of content blocks, got number"),
+            "{err}"
+        );
+
+        let err = serde_json::from_str::<Message>(r#"{"role": "user", "content": null}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("expected string or array of content blocks, got null"),
+            "{err}"
+        );
+
+        let err =
+            serde_json::from_str::<Message>(r#"{"role": "user", "content": [{"type": "text"}]}"#)
+                .unwrap_err()
+                .to_string();
+        assert!(err.contains("missing field `text`"), "{err}");
+
+        let err = serde_json::from_str::<SystemMessageFormat>("7")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("expected string or array of system blocks, got number"),
+            "{err}"
+        );
+        let err = serde_json::from_str::<SystemMessageFormat>("null")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.starts_with("expected string or array of system blocks, got null"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_anthropic_response_into_completion() {
+        let completion: CompletionResponse =
+            serde_json::from_str(&response_with_stop_reason(r#""end_turn""#)).unwrap();
+        let response = AnthropicResponse::Completion {
+            completion: completion.clone(),
+        };
+        assert_eq!(response.into_completion().unwrap(), completion);
+
+        let err = AnthropicResponse::Error {
+            error: "model not found".to_string(),
+        }
+        .into_completion()
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            AnthropicError::ApiError { status: 0, ref message } if message == "model not found"
+        ));
+
+        assert!(matches!(
+            AnthropicResponse::ListModels { models: vec![] }.into_completion(),
+            Err(AnthropicError::InvalidResponse(_))
+        ));
+    }
+
+    #[test]
+    fn test_anthropic_response_into_models() {
+        let models = vec![ModelInfo {
+            id: "claude-3-7-sonnet-20250219".to_string(),
+            display_name: "Claude 3.7 Sonnet".to_string(),
+            max_tokens: 200000,
+            provider: "anthropic".to_string(),
+            pricing: Some(ModelInfo::get_pricing("claude-3-7-sonnet-20250219")),
+        }];
+        let response = AnthropicResponse::ListModels {
+            models: models.clone(),
+        };
+        assert_eq!(response.into_models().unwrap(), models);
+
+        let err = AnthropicResponse::Error {
+            error: "unavailable".to_string(),
+        }
+        .into_models()
+        .unwrap_err();
+        assert!(matches!(err, AnthropicError::ApiError { status: 0, .. }));
+    }
+
+    #[test]
+    fn test_anthropic_response_from() {
+        let completion: CompletionResponse =
+            serde_json::from_str(&response_with_stop_reason(r#""end_turn""#)).unwrap();
+        let response = AnthropicResponse::from(completion.clone());
+        assert_eq!(
+            response,
+            AnthropicResponse::Completion {
+                completion: completion.clone()
+            }
+        );
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["Completion"]["completion"]["id"], "msg_01");
+        let roundtrip: AnthropicResponse = serde_json::from_value(json).unwrap();
+        assert_eq!(roundtrip.into_completion().unwrap(), completion);
+
+        let response: AnthropicResponse = ModelInfo::all_known().into();
+        assert!(matches!(response, AnthropicResponse::ListModels { .. }));
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(
+            json["ListModels"]["models"].as_array().unwrap().len(),
+            ModelInfo::all_known().len()
+        );
+    }
+
+    #[test]
+    fn test_anthropic_response_status() {
+        let error = AnthropicResponse::Error {
+            error: "boom".to_string(),
+        };
+        assert_eq!(error.status(), ResponseStatus::Error);
+        assert!(!error.is_success());
+
+        let models = AnthropicResponse::ListModels { models: vec![] };
+        assert_eq!(models.status(), ResponseStatus::Success);
+        assert!(models.is_success());
+    }
+
+    #[test]
+    fn test_required_betas() {
+        let mut request = test_request();
+        request.messages = vec![Message::user("Hi")];
+        assert!(request.required_betas().is_empty());
+
+        request.system = Some(SystemMessageFormat::Array(vec![SystemMessage {
+            cache_control: Some(CacheControl::ephemeral_with_ttl("1h")),
+            ..SystemMessage::text("Long instructions")
+        }]));
+        assert_eq!(
+            request.required_betas(),
+            vec!["extended-cache-ttl-2025-04-11"]
+        );
+
+        // The default 5 minute TTL needs no beta
+        request.system = Some(SystemMessageFormat::Array(vec![SystemMessage::cached(
+            "Long instructions",
+        )]));
+        assert!(request.required_betas().is_empty());
+
+        request.messages = vec![Message::user_with_blocks(vec![MessageContent::Image {
+            source: ImageSource::File {
+                file_id: "file_011".to_string(),
+            },
+            cache_control: None,
+        }])];
+        request.tools = Some(vec![AnthropicTool::computer(1024, 768, None)]);
+        request.mcp_servers = Some(vec![McpServerConfig::url(
+            "example",
+            "https://example.com/sse",
+        )]);
+        assert_eq!(
+            request.required_betas(),
+            vec![
+                "files-api-2025-04-14",
+                "computer-use-2025-01-24",
+                "mcp-client-2025-04-04"
+            ]
+        );
+
+        // October 2024 tool versions share one beta
+        request.messages = vec![Message::user("Hi")];
+        request.mcp_servers = None;
+        request.tools = Some(vec![
+            AnthropicTool::bash()
+                .with_version(crate::tools::BuiltinToolType::Bash20241022)
+                .unwrap(),
+            AnthropicTool::computer(1024, 768, None)
+                .with_version(crate::tools::BuiltinToolType::Computer20241022)
+                .unwrap(),
+        ]);
+        assert_eq!(request.required_betas(), vec!["computer-use-2024-10-22"]);
+
+        let document = |source| MessageContent::Document {
+            source,
+            title: None,
+            context: None,
+            citations: None,
+            cache_control: None,
+        };
+        request.tools = None;
+        request.messages = vec![Message::user_with_blocks(vec![document(
+            DocumentSource::pdf(b"%PDF-1.4"),
+        )])];
+        assert!(request.required_betas().is_empty());
+
+        request.messages = vec![Message::user_with_blocks(vec![document(
+            DocumentSource::File {
+                file_id: "file_011".to_string(),
+            },
+        )])];
+        assert_eq!(request.required_betas(), vec!["files-api-2025-04-14"]);
+    }
+
+    #[test]
+    fn test_redacted_request() {
+        let image_data = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk";
+        let long_text = "confidential ".repeat(40);
+        let mut request = test_request();
+        request.messages = vec![
+            Message::user_with_blocks(vec![
+                MessageContent::Image {
+                    source: ImageSource::Base64 {
+                        media_type: MediaType::Png,
+                        data: image_data.to_string(),
+                    },
+                    cache_control: None,
+                },
+                MessageContent::text(long_text.as_str()),
+                MessageContent::text("What is in this image?"),
+                MessageContent::Document {
+                    source: DocumentSource::pdf(b"%PDF-1.4 secret report"),
+                    title: Some("Report".to_string()),
+                    context: None,
+                    citations: None,
+                    cache_control: None,
+                },
+            ]),
+            Message::assistant("A single pixel."),
+        ];
+        request.mcp_servers = Some(vec![McpServerConfig {
+            authorization_token: Some("secret-token".to_string()),
+            ..McpServerConfig::url("example", "https://example.com/sse")
+        }]);
+
+        let redacted = request.redacted();
+        let debug = format!("{:?}", redacted);
+        assert!(!debug.contains(image_data));
+        assert!(!debug.contains(&crate::base64::encode(b"%PDF-1.4 secret report")));
+        assert!(!debug.contains("confidential"));
+        assert!(!debug.contains("secret-token"));
+        assert!(debug.contains(&format!("<redacted {} bytes>", image_data.len())));
+        assert!(debug.contains(&format!("<redacted {} bytes>", long_text.len())));
+
+        // Short text and the overall structure are preserved
+        assert!(debug.contains("What is in this image?"));
+        assert_eq!(redacted.messages.len(), 2);
+        assert_eq!(redacted.messages[1], Message::assistant("A single pixel."));
+    }
+
+    #[test]
+    fn test_stop_reason_display() {
+        let cases = [
+            (StopReason::EndTurn, "end_turn"),
+            (StopReason::MaxTokens, "max_tokens"),
+            (StopReason::StopSequence, "stop_sequence"),
+            (StopReason::ToolUse, "tool_use"),
+            (StopReason::PauseTurn, "pause_turn"),
+            (StopReason::Refusal, "refusal"),
+            (StopReason::Unknown, "unknown"),
+        ];
+        for (reason, expected) in cases {
+            assert_eq!(reason.to_string(), expected);
+            assert_eq!(serde_json::to_value(&reason).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_response_stop_predicates() {
+        let mut tool_use: CompletionResponse =
+            serde_json::from_str(&response_with_stop_reason(r#""tool_use""#)).unwrap();
+        assert!(tool_use.requires_tool_use());
+        assert!(!tool_use.is_complete());
+
+        // A tool use block alone is enough, whatever the stop reason
+        tool_use.stop_reason = None;
+        tool_use.content = vec![MessageContent::ToolUse {
+            id: "toolu_01".to_string(),
+            name: "get_weather".to_string(),
+            input: serde_json::json!({}),
+            cache_control: None,
+        }];
+        assert!(tool_use.requires_tool_use());
+
+        let end_turn: CompletionResponse =
+            serde_json::from_str(&response_with_stop_reason(r#""end_turn""#)).unwrap();
+        assert!(end_turn.is_complete());
+        assert!(!end_turn.requires_tool_use());
+
+        let max_tokens: CompletionResponse =
+            serde_json::from_str(&response_with_stop_reason(r#""max_tokens""#)).unwrap();
+        assert!(!max_tokens.is_complete());
+        assert!(!max_tokens.requires_tool_use());
+    }
+
+    #[test]
+    fn test_response_refusal() {
+        let mut refusal: CompletionResponse =
+            serde_json::from_str(&response_with_stop_reason(r#""refusal""#)).unwrap();
+        refusal.content = vec![MessageContent::text("I can't help with that.")];
+        assert!(refusal.is_refusal());
+        assert!(!refusal.is_complete());
+        assert_eq!(
+            refusal.refusal_text().as_deref(),
+            Some("I can't help with that.")
+        );
+
+        let mut end_turn: CompletionResponse =
+            serde_json::from_str(&response_with_stop_reason(r#""end_turn""#)).unwrap();
+        end_turn.content = vec![MessageContent::text("Here you go.")];
+        assert!(!end_turn.is_refusal());
+        assert_eq!(end_turn.refusal_text(), None);
+    }
+
+    #[test]
+    fn test_validate_prefill() {
+        let mut request = test_request();
+        request.messages = vec![Message::user("List three colors"), Message::assistant("1.")];
+        assert_eq!(request.validate_prefill(), Ok(()));
+
+        request.messages[1] = Message::assistant("1. ");
+        assert_eq!(
+            request.validate_prefill(),
+            Err(r#"assistant prefill must not end with whitespace: "1. ""#.to_string())
+        );
+
+        request.messages[1] = Message::new_structured(
+            Role::Assistant,
+            vec![MessageContent::text("Here is the JSON:\n")],
+        );
+        assert!(request.validate_prefill().is_err());
+
+        // Whitespace before a trailing non-text block is fine
+        request.messages[1] = Message::new_structured(
+            Role::Assistant,
+            vec![
+                MessageContent::text("Checking. "),
+                MessageContent::ToolUse {
+                    id: "toolu_01".to_string(),
+                    name: "get_weather".to_string(),
+                    input: serde_json::json!({}),
+                    cache_control: None,
+                },
             ],
-            "messages": [
-              {
-                "role": "user",
-                "content": "Analyze the major themes in Pride and Prejudice."
-              }
+        );
+        assert_eq!(request.validate_prefill(), Ok(()));
+
+        // A trailing user turn is not a prefill
+        request.messages = vec![Message::user("Hi ")];
+        assert_eq!(request.validate_prefill(), Ok(()));
+    }
+
+    #[test]
+    fn test_search_result_block() {
+        let json = serde_json::json!({
+            "type": "search_result",
+            "source": "https://example.com/docs/guide",
+            "title": "Getting started",
+            "content": [
+                {"type": "text", "text": "Install the package."},
+                {"type": "text", "text": "Run the setup command."}
+            ],
+            "citations": {"enabled": true}
+        });
+        let block: MessageContent = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(
+            block,
+            MessageContent::SearchResult {
+                source: "https://example.com/docs/guide".to_string(),
+                title: "Getting started".to_string(),
+                content: vec![
+                    ChunkedText::new("Install the package."),
+                    ChunkedText::new("Run the setup command."),
+                ],
+                citations: Some(DocumentCitations { enabled: true }),
+                cache_control: None,
+            }
+        );
+        assert_eq!(serde_json::to_value(&block).unwrap(), json);
+    }
+
+    #[test]
+    fn test_message_from_response() {
+        let mut response: CompletionResponse =
+            serde_json::from_str(&response_with_stop_reason(r#""tool_use""#)).unwrap();
+        response.content = vec![
+            MessageContent::text("Let me check."),
+            MessageContent::ToolUse {
+                id: "toolu_01".to_string(),
+                name: "get_weather".to_string(),
+                input: serde_json::json!({"location": "Paris"}),
+                cache_control: None,
+            },
+        ];
+
+        let message = Message::from(&response);
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!({
+                "role": "assistant",
+                "content": [
+                    {"type": "text", "text": "Let me check."},
+                    {
+                        "type": "tool_use",
+                        "id": "toolu_01",
+                        "name": "get_weather",
+                        "input": {"location": "Paris"}
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_tool_result_with_image() {
+        let block = MessageContent::tool_result_blocks(
+            "toolu_01",
+            vec![
+                ToolContent::Text {
+                    text: "Screenshot attached".to_string(),
+                },
+                ToolContent::Image {
+                    data: "iVBORw0KGgo=".to_string(),
+                    mime_type: "image/png".to_string(),
+                },
+            ],
+            None,
+        );
+        let json = serde_json::json!({
+            "type": "tool_result",
+            "tool_use_id": "toolu_01",
+            "content": [
+                {"type": "text", "text": "Screenshot attached"},
+                {
+                    "type": "image",
+                    "source": {"type": "base64", "media_type": "image/png", "data": "iVBORw0KGgo="}
+                }
             ]
-          }"#;
+        });
+        assert_eq!(serde_json::to_value(&block).unwrap(), json);
 
-        serde_json::from_str::<CompletionRequest>(json).expect("Failed to deserialize request");
+        let parsed: MessageContent = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, block);
     }
 
     #[test]
-    fn test_deserialize_completion_request_with_system_message_string() {
-        let json = r#"{
-            "model": "claude-3-7-sonnet-20250219",
-            "max_tokens": 1024,
-            "system": "You are an AI assistant tasked with analyzing literary works. Your goal is to provide insightful commentary on themes, characters, and writing style.",
-            "messages": [
-              {
-                "role": "user",
-                "content": "Analyze the major themes in Pride and Prejudice."
-              }
+    fn test_tool_result_string_content() {
+        let parsed: MessageContent = serde_json::from_str(
+            r#"{"type": "tool_result", "tool_use_id": "toolu_01", "content": "15 degrees"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            parsed,
+            MessageContent::tool_result("toolu_01", "15 degrees")
+        );
+    }
+
+    #[test]
+    fn test_validate_message_order() {
+        let mut request = test_request();
+        request.messages = vec![
+            Message::user("Hi"),
+            Message::assistant("Hello"),
+            Message::user("How are you?"),
+        ];
+        assert_eq!(request.validate_message_order(), Ok(()));
+
+        request.messages = vec![Message::assistant("Hello"), Message::user("Hi")];
+        assert_eq!(
+            request.validate_message_order(),
+            Err("the first message must be from the user, got assistant".to_string())
+        );
+
+        request.messages = vec![
+            Message::user("Hi"),
+            Message::user("Are you there?"),
+            Message::assistant("Yes"),
+        ];
+        assert_eq!(
+            request.validate_message_order(),
+            Err("messages 0 and 1 are both from the user".to_string())
+        );
+    }
+
+    #[test]
+    fn test_set_cache_breakpoint_on_last_block() {
+        let mut request = test_request();
+        request.messages = vec![Message::user("Hi"), Message::user("Summarize this")];
+        request.set_cache_breakpoint_on_last_block();
+
+        assert_eq!(request.messages[0], Message::user("Hi"));
+        assert_eq!(
+            request.messages[1],
+            Message::user_with_blocks(vec![MessageContent::Text {
+                text: "Summarize this".to_string(),
+                citations: None,
+                cache_control: Some(CacheControl::ephemeral()),
+            }])
+        );
+        assert_eq!(request.count_cache_breakpoints(), 1);
+
+        // A trailing tool use is marked itself
+        let tool_use = MessageContent::ToolUse {
+            id: "toolu_01".to_string(),
+            name: "get_weather".to_string(),
+            input: serde_json::json!({}),
+            cache_control: None,
+        };
+        request.messages = vec![Message::new_structured(
+            Role::Assistant,
+            vec![MessageContent::text("Let me check."), tool_use],
+        )];
+        request.set_cache_breakpoint_on_last_block();
+        let blocks = request.messages[0].content_blocks();
+        assert_eq!(blocks[0].cache_control(), None);
+        assert_eq!(blocks[1].cache_control(), Some(&CacheControl::ephemeral()));
+        assert_eq!(
+            serde_json::to_value(&blocks[1]).unwrap()["cache_control"],
+            serde_json::json!({"type": "ephemeral"})
+        );
+
+        // Thinking blocks can't be marked directly, so the block before is used
+        request.messages = vec![Message::new_structured(
+            Role::Assistant,
+            vec![
+                MessageContent::text("Let me check."),
+                MessageContent::Thinking {
+                    thinking: "The user wants the weather".to_string(),
+                    signature: "sig".to_string(),
+                },
+            ],
+        )];
+        request.set_cache_breakpoint_on_last_block();
+        let blocks = request.messages[0].content_blocks();
+        assert_eq!(blocks[0].cache_control(), Some(&CacheControl::ephemeral()));
+        assert_eq!(blocks[1].cache_control(), None);
+    }
+
+    #[test]
+    fn test_validate_tool_choice() {
+        let mut request = test_request();
+        assert_eq!(request.validate_tool_choice(), Ok(()));
+
+        request.tool_choice = Some(ToolChoice::specific("get_weather"));
+        assert_eq!(
+            request.validate_tool_choice(),
+            Err("tool_choice tool(get_weather) requires at least one tool".to_string())
+        );
+
+        request.tools = Some(vec![AnthropicTool::custom(
+            "get_time",
+            "Get the current time",
+            serde_json::json!({"type": "object"}),
+        )]);
+        assert_eq!(
+            request.validate_tool_choice(),
+            Err("tool_choice references unknown tool \"get_weather\"".to_string())
+        );
+
+        request.tool_choice = Some(ToolChoice::specific("get_time"));
+        assert_eq!(request.validate_tool_choice(), Ok(()));
+
+        // none may be sent with or without tools
+        request.tool_choice = Some(ToolChoice::none());
+        assert_eq!(request.validate_tool_choice(), Ok(()));
+        request.tools = None;
+        assert_eq!(request.validate_tool_choice(), Ok(()));
+
+        request.tool_choice = Some(ToolChoice::any());
+        assert!(request.validate_tool_choice().is_err());
+    }
+
+    #[test]
+    fn test_merge_consecutive_roles() {
+        let mut request = test_request();
+        request.messages = vec![
+            Message::user("Hi"),
+            Message::user_with_blocks(vec![MessageContent::tool_result("toolu_01", "ok")]),
+            Message::assistant("Yes"),
+        ];
+        request.merge_consecutive_roles();
+
+        assert_eq!(request.validate_message_order(), Ok(()));
+        assert_eq!(
+            request.messages,
+            vec![
+                Message::user_with_blocks(vec![
+                    MessageContent::tool_result("toolu_01", "ok"),
+                    MessageContent::text("Hi"),
+                ]),
+                Message::assistant("Yes"),
             ]
-        }"#;
+        );
 
-        serde_json::from_str::<CompletionRequest>(json).expect("Failed to deserialize request");
+        // Assistant turns keep their order
+        request.messages = vec![Message::assistant("First"), Message::assistant("Second")];
+        request.merge_consecutive_roles();
+        assert_eq!(
+            request.messages,
+            vec![Message::new_structured(
+                Role::Assistant,
+                vec![
+                    MessageContent::text("First"),
+                    MessageContent::text("Second")
+                ],
+            )]
+        );
+    }
+
+    #[test]
+    fn test_document_source_validate() {
+        let url = |url: &str| DocumentSource::Url {
+            url: url.to_string(),
+        };
+        assert_eq!(url("https://example.com/report.pdf").validate(), Ok(()));
+        assert_eq!(
+            url("http://localhost:8080/report.pdf?size=2").validate(),
+            Ok(())
+        );
+        assert_eq!(url("https://[::1]/report.pdf").validate(), Ok(()));
+        assert_eq!(url("https://[::1]:8443/report.pdf").validate(), Ok(()));
+
+        assert_eq!(
+            url("ftp://example.com/report.pdf").validate(),
+            Err(r#"unsupported URL scheme "ftp", expected http or https"#.to_string())
+        );
+        for malformed in [
+            "example.com/report.pdf",
+            "https://",
+            "https:///report.pdf",
+            "https://exa mple.com/report.pdf",
+            "https://example..com/report.pdf",
+            "https://example.com:http/report.pdf",
+        ] {
+            assert!(url(malformed).validate().is_err(), "{malformed}");
+        }
+
+        let base64 = |media_type: &str| DocumentSource::Base64 {
+            media_type: media_type.into(),
+            data: "JVBERi0xLjQ=".to_string(),
+        };
+        assert_eq!(base64("application/pdf").validate(), Ok(()));
+        assert_eq!(
+            base64("image/png").validate(),
+            Err("unsupported document media type image/png".to_string())
+        );
+        assert_eq!(DocumentSource::text("Hello").validate(), Ok(()));
+        assert!(DocumentSource::File {
+            file_id: String::new()
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_image_source_base64_round_trip() {
+        let bytes = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0xff];
+        let source = ImageSource::base64_from_bytes("image/png", &bytes);
+        assert_eq!(
+            source,
+            ImageSource::Base64 {
+                media_type: MediaType::Png,
+                data: "iVBORw0KGgoA/w==".to_string(),
+            }
+        );
+        assert_eq!(source.decode_base64().unwrap(), bytes);
+
+        let invalid = ImageSource::Base64 {
+            media_type: MediaType::Png,
+            data: "not base64!".to_string(),
+        };
+        assert!(matches!(
+            invalid.decode_base64(),
+            Err(AnthropicError::InvalidResponse(_))
+        ));
+        let trailing = ImageSource::Base64 {
+            media_type: MediaType::Png,
+            data: "Zm9v=!!!garbage".to_string(),
+        };
+        assert!(trailing.decode_base64().is_err());
+        assert!(ImageSource::Url {
+            url: "https://example.com/cat.png".to_string()
+        }
+        .decode_base64()
+        .is_err());
+    }
+
+    #[test]
+    fn test_document_source_base64_round_trip() {
+        let bytes = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3";
+        let source = DocumentSource::base64_from_bytes("application/pdf", bytes);
+        assert_eq!(source, DocumentSource::pdf(bytes));
+        assert_eq!(
+            source,
+            DocumentSource::Base64 {
+                media_type: MediaType::Pdf,
+                data: "JVBERi0xLjQKJeLjz9M=".to_string(),
+            }
+        );
+        assert_eq!(source.decode_base64().unwrap(), bytes);
+
+        assert!(DocumentSource::text("plain").decode_base64().is_err());
+    }
+
+    #[test]
+    fn test_media_type_serialization() {
+        let known = [
+            (MediaType::Jpeg, "image/jpeg"),
+            (MediaType::Png, "image/png"),
+            (MediaType::Gif, "image/gif"),
+            (MediaType::Webp, "image/webp"),
+            (MediaType::Pdf, "application/pdf"),
+            (MediaType::PlainText, "text/plain"),
+        ];
+        for (media_type, mime) in known {
+            assert_eq!(serde_json::to_value(&media_type).unwrap(), mime);
+            assert_eq!(
+                serde_json::from_value::<MediaType>(mime.into()).unwrap(),
+                media_type
+            );
+        }
+
+        let other: MediaType = serde_json::from_str(r#""text/pdf""#).unwrap();
+        assert_eq!(other, MediaType::Other("text/pdf".to_string()));
+        assert_eq!(serde_json::to_string(&other).unwrap(), r#""text/pdf""#);
+    }
+
+    #[test]
+    #[cfg(feature = "token-estimate")]
+    fn test_estimate_tokens_close_to_count_tokens() {
+        fn assert_within_factor(estimate: u32, actual: u32) {
+            let ratio = f64::from(estimate) / f64::from(actual);
+            assert!(
+                (0.67..=1.5).contains(&ratio),
+                "estimate {} is too far from {}",
+                estimate,
+                actual
+            );
+        }
+
+        // Counts returned by the count_tokens endpoint for the documented examples
+        let simple = CompletionRequest::builder()
+            .model("claude-sonnet-4-20250514")
+            .max_tokens(1024)
+            .system("You are a scientist")
+            .message(Message::user("Hello, world"))
+            .build()
+            .unwrap();
+        assert_within_factor(simple.estimate_tokens(), 14);
+
+        let with_tool = CompletionRequest::builder()
+            .model("claude-sonnet-4-20250514")
+            .max_tokens(1024)
+            .message(Message::user("What's the weather like in San Francisco?"))
+            .tools(vec![AnthropicTool::custom(
+                "get_weather",
+                "Get the current weather in a given location",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "location": {
+                            "type": "string",
+                            "description": "The city and state, e.g. San Francisco, CA"
+                        }
+                    },
+                    "required": ["location"]
+                }),
+            )])
+            .build()
+            .unwrap();
+        assert_within_factor(with_tool.estimate_tokens(), 403);
+    }
+
+    #[test]
+    fn test_tool_result_text() {
+        let block = MessageContent::tool_result_blocks(
+            "toolu_01",
+            vec![
+                ToolContent::Text {
+                    text: "Found 2 files: ".to_string(),
+                },
+                ToolContent::Image {
+                    data: "iVBORw0KGgo=".to_string(),
+                    mime_type: "image/png".to_string(),
+                },
+                ToolContent::Text {
+                    text: "a.txt, b.txt".to_string(),
+                },
+            ],
+            None,
+        );
+        assert_eq!(
+            block.tool_result_text().as_deref(),
+            Some("Found 2 files: a.txt, b.txt")
+        );
+        assert_eq!(MessageContent::text("hi").tool_result_text(), None);
+    }
+
+    #[test]
+    fn test_tool_result_errors() {
+        let results = Message::user_with_blocks(vec![
+            MessageContent::tool_result("toolu_01", "15 degrees"),
+            MessageContent::tool_error("toolu_02", "city not found"),
+            MessageContent::tool_result_blocks("toolu_03", vec![], Some(false)),
+            MessageContent::tool_error("toolu_04", "timeout"),
+        ]);
+        assert_eq!(results.tool_result_errors(), vec!["toolu_02", "toolu_04"]);
+        assert!(Message::user("hi").tool_result_errors().is_empty());
+
+        let mut request = test_request();
+        request.messages = vec![
+            Message::user("Weather in Paris and Nowhere?"),
+            Message::assistant("Checking"),
+            results,
+        ];
+        assert_eq!(
+            request.tool_results(),
+            vec![
+                ("toolu_01", false),
+                ("toolu_02", true),
+                ("toolu_03", false),
+                ("toolu_04", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cached_system_helpers() {
+        let mut request = test_request();
+        request.with_cached_system("You are an expert on this codebase.");
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["system"],
+            serde_json::json!([{
+                "type": "text",
+                "text": "You are an expert on this codebase.",
+                "cache_control": {"type": "ephemeral"}
+            }])
+        );
+
+        request.add_cached_system_block("<codebase>...</codebase>");
+        assert_eq!(request.count_cache_breakpoints(), 2);
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["system"][1],
+            serde_json::json!({
+                "type": "text",
+                "text": "<codebase>...</codebase>",
+                "cache_control": {"type": "ephemeral"}
+            })
+        );
+
+        let mut fresh = test_request();
+        fresh.add_cached_system_block("Only block");
+        assert_eq!(fresh.count_cache_breakpoints(), 1);
+    }
+
+    #[test]
+    fn test_usage_with_server_tool_use() {
+        let usage: Usage = serde_json::from_str(
+            r#"{
+                "input_tokens": 105,
+                "output_tokens": 6039,
+                "cache_read_input_tokens": 7123,
+                "cache_creation_input_tokens": 7345,
+                "cache_creation": {
+                    "ephemeral_5m_input_tokens": 7000,
+                    "ephemeral_1h_input_tokens": 345
+                },
+                "server_tool_use": {"web_search_requests": 1}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            usage.server_tool_use,
+            Some(ServerToolUsage {
+                web_search_requests: 1
+            })
+        );
+        assert_eq!(
+            usage.cache_creation,
+            Some(CacheCreation {
+                ephemeral_5m_input_tokens: 7000,
+                ephemeral_1h_input_tokens: 345,
+            })
+        );
+
+        let total = usage.clone() + usage;
+        assert_eq!(total.server_tool_use.unwrap().web_search_requests, 2);
+        assert_eq!(total.cache_creation.unwrap().ephemeral_1h_input_tokens, 690);
+
+        let plain: Usage =
+            serde_json::from_str(r#"{"input_tokens": 1, "output_tokens": 2}"#).unwrap();
+        assert_eq!(plain.server_tool_use, None);
+        let json = serde_json::to_value(&plain).unwrap();
+        assert!(json.get("server_tool_use").is_none());
+        assert!(json.get("cache_creation").is_none());
     }
 }
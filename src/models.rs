@@ -1,7 +1,114 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+
+/// A Claude model identifier
+///
+/// Known model IDs map to dedicated variants; anything else is preserved
+/// verbatim in `Model::Other` so new models can be used before this crate
+/// knows about them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Model {
+    ClaudeOpus420250514,
+    ClaudeSonnet420250514,
+    Claude37Sonnet20250219,
+    Claude35Sonnet20241022,
+    Claude35Haiku20241022,
+    Claude35Sonnet20240620,
+    Claude3Opus20240229,
+    Claude3Sonnet20240229,
+    Claude3Haiku20240307,
+    Claude21,
+    Claude20,
+    /// A model ID not known to this crate
+    Other(String),
+}
+
+impl Model {
+    /// All model IDs known to this crate
+    pub const KNOWN: &'static [Model] = &[
+        Model::ClaudeOpus420250514,
+        Model::ClaudeSonnet420250514,
+        Model::Claude37Sonnet20250219,
+        Model::Claude35Sonnet20241022,
+        Model::Claude35Haiku20241022,
+        Model::Claude35Sonnet20240620,
+        Model::Claude3Opus20240229,
+        Model::Claude3Sonnet20240229,
+        Model::Claude3Haiku20240307,
+        Model::Claude21,
+        Model::Claude20,
+    ];
+
+    /// Get the model ID as sent over the wire
+    pub fn as_str(&self) -> &str {
+        match self {
+            Model::ClaudeOpus420250514 => "claude-opus-4-20250514",
+            Model::ClaudeSonnet420250514 => "claude-sonnet-4-20250514",
+            Model::Claude37Sonnet20250219 => "claude-3-7-sonnet-20250219",
+            Model::Claude35Sonnet20241022 => "claude-3-5-sonnet-20241022",
+            Model::Claude35Haiku20241022 => "claude-3-5-haiku-20241022",
+            Model::Claude35Sonnet20240620 => "claude-3-5-sonnet-20240620",
+            Model::Claude3Opus20240229 => "claude-3-opus-20240229",
+            Model::Claude3Sonnet20240229 => "claude-3-sonnet-20240229",
+            Model::Claude3Haiku20240307 => "claude-3-haiku-20240307",
+            Model::Claude21 => "claude-2.1",
+            Model::Claude20 => "claude-2.0",
+            Model::Other(id) => id,
+        }
+    }
+}
+
+impl From<&str> for Model {
+    fn from(model_id: &str) -> Self {
+        match model_id {
+            "claude-opus-4-20250514" => Model::ClaudeOpus420250514,
+            "claude-sonnet-4-20250514" => Model::ClaudeSonnet420250514,
+            "claude-3-7-sonnet-20250219" => Model::Claude37Sonnet20250219,
+            "claude-3-5-sonnet-20241022" => Model::Claude35Sonnet20241022,
+            "claude-3-5-haiku-20241022" => Model::Claude35Haiku20241022,
+            "claude-3-5-sonnet-20240620" => Model::Claude35Sonnet20240620,
+            "claude-3-opus-20240229" => Model::Claude3Opus20240229,
+            "claude-3-sonnet-20240229" => Model::Claude3Sonnet20240229,
+            "claude-3-haiku-20240307" => Model::Claude3Haiku20240307,
+            "claude-2.1" => Model::Claude21,
+            "claude-2.0" => Model::Claude20,
+            other => Model::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for Model {
+    fn from(model_id: String) -> Self {
+        Model::from(model_id.as_str())
+    }
+}
+
+impl AsRef<str> for Model {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Model {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Model {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Model::from)
+    }
+}
 
 /// Information about a model
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ModelInfo {
     /// Model ID
     pub id: String,
@@ -20,78 +127,548 @@ pub struct ModelInfo {
 }
 
 /// Pricing information for a model
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ModelPricing {
     /// Cost per million input tokens
     pub input_cost_per_million_tokens: f64,
 
     /// Cost per million output tokens
     pub output_cost_per_million_tokens: f64,
+
+    /// Cost per million tokens written to the prompt cache (5 minute TTL)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_write_cost_per_million_tokens: Option<f64>,
+
+    /// Cost per million tokens read from the prompt cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_read_cost_per_million_tokens: Option<f64>,
+}
+
+impl ModelPricing {
+    /// Multiplier applied to the input price for tokens written to the prompt cache
+    pub const CACHE_WRITE_MULTIPLIER: f64 = 1.25;
+
+    /// Multiplier applied to the input price for prompt cache writes with the 1 hour TTL
+    pub const CACHE_WRITE_1H_MULTIPLIER: f64 = 2.0;
+
+    /// Multiplier applied to the input price for tokens read from the prompt cache
+    pub const CACHE_READ_MULTIPLIER: f64 = 0.1;
+
+    /// Cost per million cache write tokens, derived from the input price if not set
+    pub fn effective_cache_write_cost(&self) -> f64 {
+        self.cache_write_cost_per_million_tokens
+            .unwrap_or(self.input_cost_per_million_tokens * Self::CACHE_WRITE_MULTIPLIER)
+    }
+
+    /// Cost per million cache write tokens with the 1 hour TTL, derived from the input price
+    pub fn effective_cache_write_1h_cost(&self) -> f64 {
+        self.input_cost_per_million_tokens * Self::CACHE_WRITE_1H_MULTIPLIER
+    }
+
+    /// Cost per million cache read tokens, derived from the input price if not set
+    pub fn effective_cache_read_cost(&self) -> f64 {
+        self.cache_read_cost_per_million_tokens
+            .unwrap_or(self.input_cost_per_million_tokens * Self::CACHE_READ_MULTIPLIER)
+    }
+
+    /// Blended cost per million tokens for a workload with the given share of input tokens
+    ///
+    /// `input_ratio` is the fraction of tokens that are input, clamped to `0.0..=1.0`.
+    /// A NaN ratio is treated as `0.0`.
+    pub fn blended_cost_per_million(&self, input_ratio: f64) -> f64 {
+        let input_ratio = if input_ratio.is_nan() {
+            0.0
+        } else {
+            input_ratio.clamp(0.0, 1.0)
+        };
+        self.input_cost_per_million_tokens * input_ratio
+            + self.output_cost_per_million_tokens * (1.0 - input_ratio)
+    }
 }
 
 impl ModelInfo {
-    /// Get maximum tokens for a given model ID
-    pub fn get_max_tokens(model_id: &str) -> u32 {
+    /// Map an undated alias such as `claude-3-7-sonnet-latest` to the dated
+    /// snapshot it currently points to
+    ///
+    /// Model IDs that are not known aliases are returned unchanged. All lookups
+    /// on `ModelInfo` resolve aliases first.
+    pub fn resolve_alias(model_id: &str) -> &str {
+        match model_id {
+            "claude-opus-4-0" => "claude-opus-4-20250514",
+            "claude-sonnet-4-0" => "claude-sonnet-4-20250514",
+            "claude-3-7-sonnet-latest" => "claude-3-7-sonnet-20250219",
+            "claude-3-5-sonnet-latest" => "claude-3-5-sonnet-20241022",
+            "claude-3-5-haiku-latest" => "claude-3-5-haiku-20241022",
+            "claude-3-opus-latest" => "claude-3-opus-20240229",
+            other => other,
+        }
+    }
+
+    /// Get maximum tokens for a given model ID or `Model`
+    pub fn get_max_tokens<M: AsRef<str> + ?Sized>(model: &M) -> u32 {
+        // Conservative default for unknown models
+        Self::known_max_tokens(Self::resolve_alias(model.as_ref())).unwrap_or(100000)
+    }
+
+    /// Get the maximum number of output tokens for a given model ID or `Model`
+    pub fn max_output_tokens<M: AsRef<str> + ?Sized>(model: &M) -> u32 {
+        // Conservative default for unknown models
+        Self::known_max_output_tokens(Self::resolve_alias(model.as_ref())).unwrap_or(4096)
+    }
+
+    /// Get pricing information for a given model ID or `Model`
+    pub fn get_pricing<M: AsRef<str> + ?Sized>(model: &M) -> ModelPricing {
+        // Default for unknown models
+        Self::known_pricing(Self::resolve_alias(model.as_ref())).unwrap_or(ModelPricing {
+            input_cost_per_million_tokens: 8.00,
+            output_cost_per_million_tokens: 24.00,
+            cache_write_cost_per_million_tokens: None,
+            cache_read_cost_per_million_tokens: None,
+        })
+    }
+
+    /// Build a fully populated `ModelInfo` for every model known to this crate
+    pub fn all_known() -> Vec<ModelInfo> {
+        Model::KNOWN
+            .iter()
+            .map(|model| ModelInfo {
+                id: model.as_str().to_string(),
+                display_name: Self::display_name_for(model),
+                max_tokens: Self::get_max_tokens(model),
+                provider: "anthropic".to_string(),
+                pricing: Some(Self::get_pricing(model)),
+            })
+            .collect()
+    }
+
+    /// Get a human-readable name for a given model ID or `Model`
+    ///
+    /// Unknown IDs are title-cased word by word, so `claude-future-model`
+    /// becomes `Claude Future Model`.
+    pub fn display_name_for<M: AsRef<str> + ?Sized>(model: &M) -> String {
+        let model_id = Self::resolve_alias(model.as_ref());
+        if let Some(name) = Self::known_display_name(model_id) {
+            return name.to_string();
+        }
+        model_id
+            .split(['-', '_'])
+            .filter(|word| !word.is_empty())
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// Whether a given model ID or `Model` supports tool use
+    ///
+    /// Unknown models are assumed to be recent enough to support tools.
+    pub fn supports_tools<M: AsRef<str> + ?Sized>(model: &M) -> bool {
+        Self::known_capabilities(Self::resolve_alias(model.as_ref()))
+            .is_none_or(|(tools, _, _)| tools)
+    }
+
+    /// Whether a given model ID or `Model` accepts image input
+    ///
+    /// Unknown models are assumed to be recent enough to accept images.
+    pub fn supports_vision<M: AsRef<str> + ?Sized>(model: &M) -> bool {
+        Self::known_capabilities(Self::resolve_alias(model.as_ref()))
+            .is_none_or(|(_, vision, _)| vision)
+    }
+
+    /// Whether a given model ID or `Model` supports extended thinking
+    ///
+    /// Unknown models are assumed not to, since sending a thinking config to a
+    /// model without it fails the request.
+    pub fn supports_extended_thinking<M: AsRef<str> + ?Sized>(model: &M) -> bool {
+        Self::known_capabilities(Self::resolve_alias(model.as_ref()))
+            .is_some_and(|(_, _, thinking)| thinking)
+    }
+
+    fn known_display_name(model_id: &str) -> Option<&'static str> {
+        match model_id {
+            // Claude 4 models
+            "claude-opus-4-20250514" => Some("Claude Opus 4"),
+            "claude-sonnet-4-20250514" => Some("Claude Sonnet 4"),
+
+            // Claude 3.7 models
+            "claude-3-7-sonnet-20250219" => Some("Claude 3.7 Sonnet"),
+
+            // Claude 3.5 models
+            "claude-3-5-sonnet-20241022" | "claude-3-5-sonnet-20240620" => {
+                Some("Claude 3.5 Sonnet")
+            }
+            "claude-3-5-haiku-20241022" => Some("Claude 3.5 Haiku"),
+
+            // Claude 3 models
+            "claude-3-opus-20240229" => Some("Claude 3 Opus"),
+            "claude-3-sonnet-20240229" => Some("Claude 3 Sonnet"),
+            "claude-3-haiku-20240307" => Some("Claude 3 Haiku"),
+
+            // Claude 2 models
+            "claude-2.1" => Some("Claude 2.1"),
+            "claude-2.0" => Some("Claude 2.0"),
+
+            _ => None,
+        }
+    }
+
+    fn known_max_tokens(model_id: &str) -> Option<u32> {
         match model_id {
+            // Claude 4 models
+            "claude-opus-4-20250514" | "claude-sonnet-4-20250514" => Some(200000),
+
             // Claude 3.7 models
-            "claude-3-7-sonnet-20250219" => 200000,
+            "claude-3-7-sonnet-20250219" => Some(200000),
 
             // Claude 3.5 models
             "claude-3-5-sonnet-20241022"
             | "claude-3-5-haiku-20241022"
-            | "claude-3-5-sonnet-20240620" => 200000,
+            | "claude-3-5-sonnet-20240620" => Some(200000),
 
             // Claude 3 models
-            "claude-3-opus-20240229" => 200000,
-            "claude-3-sonnet-20240229" => 200000,
-            "claude-3-haiku-20240307" => 200000,
+            "claude-3-opus-20240229" => Some(200000),
+            "claude-3-sonnet-20240229" => Some(200000),
+            "claude-3-haiku-20240307" => Some(200000),
 
             // Claude 2 models
-            "claude-2.1" | "claude-2.0" => 100000,
+            "claude-2.1" | "claude-2.0" => Some(100000),
 
-            // Default case
-            _ => 100000, // Conservative default
+            _ => None,
         }
     }
 
-    /// Get pricing information for a given model ID
-    pub fn get_pricing(model_id: &str) -> ModelPricing {
+    pub(crate) fn known_max_output_tokens(model_id: &str) -> Option<u32> {
         match model_id {
+            // Claude 4 models
+            "claude-opus-4-20250514" => Some(32000),
+            "claude-sonnet-4-20250514" => Some(64000),
+
             // Claude 3.7 models
-            "claude-3-7-sonnet-20250219" => ModelPricing {
-                input_cost_per_million_tokens: 3.00,
-                output_cost_per_million_tokens: 15.00,
-            },
+            "claude-3-7-sonnet-20250219" => Some(64000),
 
             // Claude 3.5 models
-            "claude-3-5-sonnet-20241022" | "claude-3-5-sonnet-20240620" => ModelPricing {
-                input_cost_per_million_tokens: 3.00,
-                output_cost_per_million_tokens: 15.00,
-            },
-            "claude-3-5-haiku-20241022" => ModelPricing {
-                input_cost_per_million_tokens: 0.80,
-                output_cost_per_million_tokens: 4.00,
-            },
+            "claude-3-5-sonnet-20241022"
+            | "claude-3-5-haiku-20241022"
+            | "claude-3-5-sonnet-20240620" => Some(8192),
 
             // Claude 3 models
-            "claude-3-opus-20240229" => ModelPricing {
-                input_cost_per_million_tokens: 15.00,
-                output_cost_per_million_tokens: 75.00,
-            },
-            "claude-3-haiku-20240307" => ModelPricing {
-                input_cost_per_million_tokens: 0.25,
-                output_cost_per_million_tokens: 1.25,
-            },
-            "claude-3-sonnet-20240229" => ModelPricing {
-                input_cost_per_million_tokens: 3.00,
-                output_cost_per_million_tokens: 15.00,
-            },
-
-            // Default for older or unknown models
-            _ => ModelPricing {
-                input_cost_per_million_tokens: 8.00,
-                output_cost_per_million_tokens: 24.00,
-            },
+            "claude-3-opus-20240229" | "claude-3-sonnet-20240229" | "claude-3-haiku-20240307" => {
+                Some(4096)
+            }
+
+            // Claude 2 models
+            "claude-2.1" | "claude-2.0" => Some(4096),
+
+            _ => None,
+        }
+    }
+
+    fn known_capabilities(model_id: &str) -> Option<(bool, bool, bool)> {
+        // (tools, vision, extended thinking)
+        match model_id {
+            // Claude 4 models
+            "claude-opus-4-20250514" | "claude-sonnet-4-20250514" => Some((true, true, true)),
+
+            // Claude 3.7 models
+            "claude-3-7-sonnet-20250219" => Some((true, true, true)),
+
+            // Claude 3.5 models
+            "claude-3-5-sonnet-20241022"
+            | "claude-3-5-haiku-20241022"
+            | "claude-3-5-sonnet-20240620" => Some((true, true, false)),
+
+            // Claude 3 models
+            "claude-3-opus-20240229" | "claude-3-sonnet-20240229" | "claude-3-haiku-20240307" => {
+                Some((true, true, false))
+            }
+
+            // Claude 2 models
+            "claude-2.1" | "claude-2.0" => Some((false, false, false)),
+
+            _ => None,
+        }
+    }
+
+    fn known_pricing(model_id: &str) -> Option<ModelPricing> {
+        // (input, output, cache write, cache read)
+        let (input, output, cache_write, cache_read) = match model_id {
+            // Claude 4 models
+            "claude-opus-4-20250514" => (15.00, 75.00, Some(18.75), Some(1.50)),
+            "claude-sonnet-4-20250514" => (3.00, 15.00, Some(3.75), Some(0.30)),
+
+            // Claude 3.7 models
+            "claude-3-7-sonnet-20250219" => (3.00, 15.00, Some(3.75), Some(0.30)),
+
+            // Claude 3.5 models
+            "claude-3-5-sonnet-20241022" | "claude-3-5-sonnet-20240620" => {
+                (3.00, 15.00, Some(3.75), Some(0.30))
+            }
+            "claude-3-5-haiku-20241022" => (0.80, 4.00, Some(1.00), Some(0.08)),
+
+            // Claude 3 models
+            "claude-3-opus-20240229" => (15.00, 75.00, Some(18.75), Some(1.50)),
+            "claude-3-haiku-20240307" => (0.25, 1.25, Some(0.30), Some(0.03)),
+            "claude-3-sonnet-20240229" => (3.00, 15.00, None, None),
+
+            // Claude 2 models
+            "claude-2.1" | "claude-2.0" => (8.00, 24.00, None, None),
+
+            _ => return None,
+        };
+
+        Some(ModelPricing {
+            input_cost_per_million_tokens: input,
+            output_cost_per_million_tokens: output,
+            cache_write_cost_per_million_tokens: cache_write,
+            cache_read_cost_per_million_tokens: cache_read,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_string_conversion() {
+        let model = Model::from("claude-3-7-sonnet-20250219");
+        assert_eq!(model, Model::Claude37Sonnet20250219);
+        assert_eq!(model.as_str(), "claude-3-7-sonnet-20250219");
+
+        let unknown = Model::from("claude-future-model");
+        assert_eq!(unknown, Model::Other("claude-future-model".to_string()));
+        assert_eq!(unknown.as_str(), "claude-future-model");
+    }
+
+    #[test]
+    fn test_model_serde() {
+        let json = serde_json::to_string(&Model::Claude3Haiku20240307).unwrap();
+        assert_eq!(json, r#""claude-3-haiku-20240307""#);
+
+        let model: Model = serde_json::from_str(r#""claude-2.1""#).unwrap();
+        assert_eq!(model, Model::Claude21);
+
+        let model: Model = serde_json::from_str(r#""some-new-model""#).unwrap();
+        assert_eq!(model, Model::Other("some-new-model".to_string()));
+    }
+
+    #[test]
+    fn test_lookups_accept_model_and_str() {
+        let model = Model::Claude3Opus20240229;
+        assert_eq!(
+            ModelInfo::get_max_tokens(&model),
+            ModelInfo::get_max_tokens("claude-3-opus-20240229")
+        );
+        assert_eq!(
+            ModelInfo::get_pricing(&model).input_cost_per_million_tokens,
+            ModelInfo::get_pricing("claude-3-opus-20240229").input_cost_per_million_tokens
+        );
+    }
+
+    #[test]
+    fn test_claude_4_pricing() {
+        let opus = ModelInfo::get_pricing("claude-opus-4-20250514");
+        assert_eq!(opus.input_cost_per_million_tokens, 15.00);
+        assert_eq!(opus.output_cost_per_million_tokens, 75.00);
+
+        let sonnet = ModelInfo::get_pricing("claude-sonnet-4-20250514");
+        assert_eq!(sonnet.input_cost_per_million_tokens, 3.00);
+        assert_eq!(sonnet.output_cost_per_million_tokens, 15.00);
+
+        assert_eq!(ModelInfo::get_max_tokens("claude-opus-4-20250514"), 200000);
+        assert_eq!(
+            ModelInfo::get_max_tokens("claude-sonnet-4-20250514"),
+            200000
+        );
+    }
+
+    #[test]
+    fn test_model_tables_in_sync() {
+        for model in Model::KNOWN {
+            assert_eq!(&Model::from(model.as_str()), model);
+            assert!(
+                ModelInfo::known_max_tokens(model.as_str()).is_some(),
+                "{} is missing from the max tokens table",
+                model
+            );
+            assert!(
+                ModelInfo::known_max_output_tokens(model.as_str()).is_some(),
+                "{} is missing from the max output tokens table",
+                model
+            );
+            assert!(
+                ModelInfo::known_pricing(model.as_str()).is_some(),
+                "{} is missing from the pricing table",
+                model
+            );
+            assert!(
+                ModelInfo::known_display_name(model.as_str()).is_some(),
+                "{} is missing from the display name table",
+                model
+            );
+            assert!(
+                ModelInfo::known_capabilities(model.as_str()).is_some(),
+                "{} is missing from the capabilities table",
+                model
+            );
         }
     }
+
+    #[test]
+    fn test_cache_pricing() {
+        let pricing = ModelInfo::get_pricing("claude-3-5-sonnet-20241022");
+        let input = pricing.input_cost_per_million_tokens;
+        let cache_write = pricing.cache_write_cost_per_million_tokens.unwrap();
+        let cache_read = pricing.cache_read_cost_per_million_tokens.unwrap();
+        assert!((cache_write - input * ModelPricing::CACHE_WRITE_MULTIPLIER).abs() < 1e-9);
+        assert!((cache_read - input * ModelPricing::CACHE_READ_MULTIPLIER).abs() < 1e-9);
+        assert!((pricing.effective_cache_write_1h_cost() - input * 2.0).abs() < 1e-9);
+
+        let legacy: ModelPricing = serde_json::from_str(
+            r#"{"input_cost_per_million_tokens": 3.0, "output_cost_per_million_tokens": 15.0}"#,
+        )
+        .expect("Failed to deserialize pricing without cache fields");
+        assert_eq!(legacy.cache_write_cost_per_million_tokens, None);
+        assert!((legacy.effective_cache_write_cost() - 3.75).abs() < 1e-9);
+        assert!((legacy.effective_cache_read_cost() - 0.30).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_blended_cost_per_million() {
+        let pricing = ModelInfo::get_pricing("claude-3-5-sonnet-20241022");
+        // 3.00 * 0.9 + 15.00 * 0.1
+        assert!((pricing.blended_cost_per_million(0.9) - 4.2).abs() < 1e-9);
+        assert!((pricing.blended_cost_per_million(1.0) - 3.0).abs() < 1e-9);
+        assert!((pricing.blended_cost_per_million(0.0) - 15.0).abs() < 1e-9);
+
+        // Out of range ratios are clamped
+        assert!((pricing.blended_cost_per_million(1.5) - 3.0).abs() < 1e-9);
+        assert!((pricing.blended_cost_per_million(-0.5) - 15.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_max_output_tokens() {
+        assert_eq!(
+            ModelInfo::max_output_tokens("claude-3-7-sonnet-20250219"),
+            64000
+        );
+        assert_eq!(
+            ModelInfo::max_output_tokens("claude-3-5-haiku-20241022"),
+            8192
+        );
+        assert_eq!(
+            ModelInfo::max_output_tokens(&Model::Claude3Opus20240229),
+            4096
+        );
+        assert_eq!(ModelInfo::max_output_tokens("unknown-model"), 4096);
+    }
+
+    #[test]
+    fn test_capability_matrix() {
+        // (model, tools, vision, extended thinking)
+        let matrix = [
+            ("claude-opus-4-20250514", true, true, true),
+            ("claude-3-7-sonnet-20250219", true, true, true),
+            ("claude-3-5-sonnet-20241022", true, true, false),
+            ("claude-3-haiku-20240307", true, true, false),
+            ("claude-2.1", false, false, false),
+        ];
+        for (model, tools, vision, thinking) in matrix {
+            assert_eq!(ModelInfo::supports_tools(model), tools, "{}", model);
+            assert_eq!(ModelInfo::supports_vision(model), vision, "{}", model);
+            assert_eq!(
+                ModelInfo::supports_extended_thinking(model),
+                thinking,
+                "{}",
+                model
+            );
+        }
+
+        assert!(ModelInfo::supports_tools(&Model::Claude35Haiku20241022));
+        assert!(ModelInfo::supports_tools("claude-future-model"));
+        assert!(!ModelInfo::supports_extended_thinking(
+            "claude-future-model"
+        ));
+    }
+
+    #[test]
+    fn test_all_known_models() {
+        let models = ModelInfo::all_known();
+        assert_eq!(models.len(), Model::KNOWN.len());
+        for info in &models {
+            assert!(info.pricing.is_some(), "{} has no pricing", info.id);
+            assert_eq!(info.provider, "anthropic");
+            assert_ne!(info.display_name, info.id);
+        }
+
+        let sonnet = models
+            .iter()
+            .find(|info| info.id == "claude-3-7-sonnet-20250219")
+            .unwrap();
+        assert_eq!(sonnet.display_name, "Claude 3.7 Sonnet");
+        assert_eq!(sonnet.max_tokens, 200000);
+        assert_eq!(
+            sonnet.pricing,
+            Some(ModelInfo::get_pricing("claude-3-7-sonnet-20250219"))
+        );
+    }
+
+    #[test]
+    fn test_display_name_for() {
+        assert_eq!(
+            ModelInfo::display_name_for("claude-3-7-sonnet-20250219"),
+            "Claude 3.7 Sonnet"
+        );
+        assert_eq!(
+            ModelInfo::display_name_for("claude-opus-4-20250514"),
+            "Claude Opus 4"
+        );
+        assert_eq!(
+            ModelInfo::display_name_for(&Model::Claude3Haiku20240307),
+            "Claude 3 Haiku"
+        );
+        assert_eq!(ModelInfo::display_name_for("claude-2.1"), "Claude 2.1");
+        assert_eq!(
+            ModelInfo::display_name_for("claude-future-model"),
+            "Claude Future Model"
+        );
+    }
+
+    #[test]
+    fn test_resolve_alias() {
+        assert_eq!(
+            ModelInfo::resolve_alias("claude-3-7-sonnet-latest"),
+            "claude-3-7-sonnet-20250219"
+        );
+        assert_eq!(
+            ModelInfo::resolve_alias("claude-sonnet-4-0"),
+            "claude-sonnet-4-20250514"
+        );
+        assert_eq!(
+            ModelInfo::resolve_alias("claude-future-model"),
+            "claude-future-model"
+        );
+
+        assert_eq!(
+            ModelInfo::get_pricing("claude-3-5-haiku-latest"),
+            ModelInfo::get_pricing("claude-3-5-haiku-20241022")
+        );
+        assert_eq!(
+            ModelInfo::max_output_tokens("claude-3-7-sonnet-latest"),
+            ModelInfo::max_output_tokens("claude-3-7-sonnet-20250219")
+        );
+        assert_eq!(
+            ModelInfo::get_max_tokens("claude-opus-4-0"),
+            ModelInfo::get_max_tokens("claude-opus-4-20250514")
+        );
+        assert_eq!(
+            ModelInfo::display_name_for("claude-3-opus-latest"),
+            "Claude 3 Opus"
+        );
+    }
 }
@@ -0,0 +1,640 @@
+use crate::errors::AnthropicError;
+use crate::messages::{
+    CompletionResponse, Container, MessageContent, Role, ServerToolUsage, StopReason, Usage,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// A single server-sent event from a streaming completion
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum StreamEvent {
+    /// Start of a new message, carrying the initial message envelope
+    #[serde(rename = "message_start")]
+    MessageStart { message: StreamMessage },
+
+    /// Start of a new content block
+    #[serde(rename = "content_block_start")]
+    ContentBlockStart(ContentBlockStart),
+
+    /// Incremental update to a content block
+    #[serde(rename = "content_block_delta")]
+    ContentBlockDelta { index: usize, delta: ContentDelta },
+
+    /// End of a content block
+    #[serde(rename = "content_block_stop")]
+    ContentBlockStop { index: usize },
+
+    /// Top-level changes to the message, such as the stop reason
+    #[serde(rename = "message_delta")]
+    MessageDelta {
+        delta: MessageDeltaBody,
+        usage: DeltaUsage,
+    },
+
+    /// End of the message
+    #[serde(rename = "message_stop")]
+    MessageStop,
+
+    /// Keep-alive event
+    #[serde(rename = "ping")]
+    Ping,
+
+    /// Error raised mid-stream
+    #[serde(rename = "error")]
+    Error { error: StreamError },
+}
+
+/// The message envelope sent in `message_start`
+///
+/// Unlike a full `CompletionResponse`, the stop reason is not yet known.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamMessage {
+    /// ID of the message
+    pub id: String,
+
+    /// Message type
+    #[serde(rename = "type")]
+    pub message_type: String,
+
+    // always "assistant"
+    pub role: Role,
+
+    /// Model used for generation
+    pub model: String,
+
+    /// Initial content blocks (usually empty)
+    pub content: Vec<MessageContent>,
+
+    /// Reason why generation stopped, always null at the start of a stream
+    pub stop_reason: Option<StopReason>,
+
+    /// Stop sequence if applicable
+    pub stop_sequence: Option<String>,
+
+    /// Token usage so far
+    pub usage: Usage,
+
+    /// Code execution container used by the request, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<Container>,
+}
+
+/// Payload of a `content_block_start` event
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContentBlockStart {
+    /// Position of the block in the message content
+    pub index: usize,
+
+    /// The initial, usually empty, block that later deltas append to
+    pub content_block: MessageContent,
+}
+
+impl ContentBlockStart {
+    /// What kind of block is starting, which decides how its deltas accumulate
+    pub fn kind(&self) -> ContentBlockKind {
+        match &self.content_block {
+            MessageContent::Text { .. } => ContentBlockKind::Text,
+            MessageContent::ToolUse { .. } => ContentBlockKind::ToolUse,
+            MessageContent::McpToolUse { .. } => ContentBlockKind::McpToolUse,
+            MessageContent::Thinking { .. } => ContentBlockKind::Thinking,
+            MessageContent::RedactedThinking { .. } => ContentBlockKind::RedactedThinking,
+            _ => ContentBlockKind::Other,
+        }
+    }
+}
+
+/// Kind of a streamed content block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentBlockKind {
+    /// Text, extended by `text_delta`
+    Text,
+    /// Tool call whose input arrives as `input_json_delta`
+    ToolUse,
+    /// MCP tool call whose input arrives as `input_json_delta`
+    McpToolUse,
+    /// Extended thinking, extended by `thinking_delta` and `signature_delta`
+    Thinking,
+    /// Redacted thinking, delivered whole
+    RedactedThinking,
+    /// Any other block, delivered whole
+    Other,
+}
+
+/// The partial response envelope sent in `message_start`
+///
+/// Alias of [`StreamMessage`] under the name used for non-streaming responses.
+pub type PartialCompletionResponse = StreamMessage;
+
+/// Incremental content for a content block
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum ContentDelta {
+    /// Text to append to a text block
+    #[serde(rename = "text_delta")]
+    TextDelta { text: String },
+
+    /// Partial JSON to append to a tool_use block's input
+    #[serde(rename = "input_json_delta")]
+    InputJsonDelta { partial_json: String },
+
+    /// Text to append to a thinking block
+    #[serde(rename = "thinking_delta")]
+    ThinkingDelta { thinking: String },
+
+    /// Signature for a thinking block
+    #[serde(rename = "signature_delta")]
+    SignatureDelta { signature: String },
+}
+
+/// Changes to the top-level message carried by `message_delta`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MessageDeltaBody {
+    /// Reason why generation stopped
+    pub stop_reason: Option<StopReason>,
+
+    /// Stop sequence if applicable
+    pub stop_sequence: Option<String>,
+
+    /// Code execution container used by the request, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<Container>,
+}
+
+/// Cumulative token usage carried by `message_delta`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DeltaUsage {
+    pub output_tokens: u32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_tokens: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_read_input_tokens: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_creation_input_tokens: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_tool_use: Option<ServerToolUsage>,
+}
+
+/// Error payload of an `error` event
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamError {
+    #[serde(rename = "type")]
+    pub error_type: String,
+
+    pub message: String,
+}
+
+/// Reassembles a stream of events into a complete `CompletionResponse`
+#[derive(Debug, Clone, Default)]
+pub struct ResponseAccumulator {
+    message: Option<StreamMessage>,
+    blocks: Vec<MessageContent>,
+    partial_json: HashMap<usize, String>,
+}
+
+impl ResponseAccumulator {
+    /// Create a new, empty accumulator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed the next event from the stream into the accumulator
+    pub fn feed(&mut self, event: StreamEvent) -> Result<(), AnthropicError> {
+        match event {
+            StreamEvent::MessageStart { message } => {
+                self.blocks = message.content.clone();
+                self.message = Some(message);
+            }
+            StreamEvent::ContentBlockStart(start) => {
+                if start.index != self.blocks.len() {
+                    return Err(AnthropicError::InvalidResponse(format!(
+                        "content_block_start for index {} but {} blocks have started",
+                        start.index,
+                        self.blocks.len()
+                    )));
+                }
+                if let ContentBlockKind::ToolUse | ContentBlockKind::McpToolUse = start.kind() {
+                    self.partial_json.insert(start.index, String::new());
+                }
+                self.blocks.push(start.content_block);
+            }
+            StreamEvent::ContentBlockDelta { index, delta } => {
+                let block = self.blocks.get_mut(index).ok_or_else(|| {
+                    AnthropicError::InvalidResponse(format!(
+                        "content_block_delta for unknown index {}",
+                        index
+                    ))
+                })?;
+                match (block, delta) {
+                    (MessageContent::Text { text, .. }, ContentDelta::TextDelta { text: more }) => {
+                        text.push_str(&more)
+                    }
+                    (
+                        MessageContent::Thinking { thinking, .. },
+                        ContentDelta::ThinkingDelta { thinking: more },
+                    ) => thinking.push_str(&more),
+                    (
+                        MessageContent::Thinking { signature, .. },
+                        ContentDelta::SignatureDelta { signature: more },
+                    ) => signature.push_str(&more),
+                    (
+                        MessageContent::ToolUse { .. } | MessageContent::McpToolUse { .. },
+                        ContentDelta::InputJsonDelta { partial_json },
+                    ) => self
+                        .partial_json
+                        .entry(index)
+                        .or_default()
+                        .push_str(&partial_json),
+                    (_, delta) => {
+                        return Err(AnthropicError::InvalidResponse(format!(
+                            "unexpected delta for content block {}: {:?}",
+                            index, delta
+                        )))
+                    }
+                }
+            }
+            StreamEvent::ContentBlockStop { index } => {
+                if let Some(json) = self.partial_json.get(&index) {
+                    let parsed = if json.trim().is_empty() {
+                        serde_json::Value::Object(Default::default())
+                    } else {
+                        serde_json::from_str(json)?
+                    };
+                    if let Some(
+                        MessageContent::ToolUse { input, .. }
+                        | MessageContent::McpToolUse { input, .. },
+                    ) = self.blocks.get_mut(index)
+                    {
+                        *input = parsed;
+                    }
+                }
+            }
+            StreamEvent::MessageDelta { delta, usage } => {
+                let message = self.message.as_mut().ok_or_else(|| {
+                    AnthropicError::InvalidResponse("message_delta before message_start".into())
+                })?;
+                if delta.stop_reason.is_some() {
+                    message.stop_reason = delta.stop_reason;
+                }
+                if delta.stop_sequence.is_some() {
+                    message.stop_sequence = delta.stop_sequence;
+                }
+                if delta.container.is_some() {
+                    message.container = delta.container;
+                }
+                message.usage.output_tokens = usage.output_tokens;
+                if let Some(input_tokens) = usage.input_tokens {
+                    message.usage.input_tokens = input_tokens;
+                }
+                if usage.cache_read_input_tokens.is_some() {
+                    message.usage.cache_read_input_tokens = usage.cache_read_input_tokens;
+                }
+                if usage.cache_creation_input_tokens.is_some() {
+                    message.usage.cache_creation_input_tokens = usage.cache_creation_input_tokens;
+                }
+                if usage.server_tool_use.is_some() {
+                    message.usage.server_tool_use = usage.server_tool_use;
+                }
+            }
+            StreamEvent::MessageStop | StreamEvent::Ping => {}
+            StreamEvent::Error { error } => {
+                // Stream errors arrive after a successful HTTP response has started
+                return Err(AnthropicError::from_error_type(
+                    200,
+                    &error.error_type,
+                    error.message,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Raw `partial_json` received so far for the tool use block at `index`
+    ///
+    /// The buffer is kept after `content_block_stop`, so it can be inspected
+    /// when the assembled input fails to parse.
+    pub fn partial_json(&self, index: usize) -> Option<&str> {
+        self.partial_json.get(&index).map(String::as_str)
+    }
+
+    /// Finish accumulating and return the assembled response
+    pub fn finish(self) -> Result<CompletionResponse, AnthropicError> {
+        let message = self.message.ok_or_else(|| {
+            AnthropicError::InvalidResponse("stream ended before message_start".into())
+        })?;
+        Ok(CompletionResponse {
+            content: self.blocks,
+            id: message.id,
+            model: message.model,
+            role: message.role,
+            stop_reason: message.stop_reason,
+            stop_sequence: message.stop_sequence,
+            message_type: message.message_type,
+            usage: message.usage,
+            container: message.container,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOOL_USE_STREAM: &[&str] = &[
+        r#"{"type":"message_start","message":{"id":"msg_014p7gG3wDgGV9EUtLvnow3U","type":"message","role":"assistant","model":"claude-3-7-sonnet-20250219","stop_sequence":null,"usage":{"input_tokens":472,"output_tokens":2},"content":[],"stop_reason":null}}"#,
+        r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+        r#"{"type":"ping"}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Okay"}}"#,
+        r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":", let's check the weather."}}"#,
+        r#"{"type":"content_block_stop","index":0}"#,
+        r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_01T1x1fJ34qAmk2tNTrN7Up6","name":"get_weather","input":{}}}"#,
+        r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":""}}"#,
+        r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"location\":"}}"#,
+        r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":" \"San Francisco, CA\"}"}}"#,
+        r#"{"type":"content_block_stop","index":1}"#,
+        r#"{"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":89}}"#,
+        r#"{"type":"message_stop"}"#,
+    ];
+
+    #[test]
+    fn test_deserialize_tool_use_stream() {
+        let events: Vec<StreamEvent> = TOOL_USE_STREAM
+            .iter()
+            .map(|data| serde_json::from_str(data).expect("Failed to deserialize event"))
+            .collect();
+
+        assert!(matches!(events[0], StreamEvent::MessageStart { .. }));
+        assert!(matches!(events[2], StreamEvent::Ping));
+        assert!(matches!(
+            events[3],
+            StreamEvent::ContentBlockDelta {
+                index: 0,
+                delta: ContentDelta::TextDelta { .. }
+            }
+        ));
+        assert!(matches!(
+            events[6],
+            StreamEvent::ContentBlockStart(ContentBlockStart {
+                index: 1,
+                content_block: MessageContent::ToolUse { .. }
+            })
+        ));
+        assert!(matches!(
+            events[8],
+            StreamEvent::ContentBlockDelta {
+                delta: ContentDelta::InputJsonDelta { .. },
+                ..
+            }
+        ));
+        match &events[11] {
+            StreamEvent::MessageDelta { delta, usage } => {
+                assert!(matches!(delta.stop_reason, Some(StopReason::ToolUse)));
+                assert_eq!(usage.output_tokens, 89);
+            }
+            other => panic!("Unexpected event: {:?}", other),
+        }
+        assert!(matches!(events[12], StreamEvent::MessageStop));
+    }
+
+    #[test]
+    fn test_deserialize_thinking_and_error_events() {
+        let start: StreamEvent = serde_json::from_str(
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"thinking","thinking":""}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            start,
+            StreamEvent::ContentBlockStart(ContentBlockStart {
+                content_block: MessageContent::Thinking { .. },
+                ..
+            })
+        ));
+
+        let delta: StreamEvent = serde_json::from_str(
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"Let me solve this step by step"}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            delta,
+            StreamEvent::ContentBlockDelta {
+                delta: ContentDelta::ThinkingDelta { .. },
+                ..
+            }
+        ));
+
+        let error: StreamEvent = serde_json::from_str(
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+        )
+        .unwrap();
+        match error {
+            StreamEvent::Error { error } => assert_eq!(error.error_type, "overloaded_error"),
+            other => panic!("Unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_accumulate_tool_use_stream() {
+        let mut accumulator = ResponseAccumulator::new();
+        for data in TOOL_USE_STREAM {
+            accumulator
+                .feed(serde_json::from_str(data).unwrap())
+                .expect("Failed to accumulate event");
+        }
+        let response = accumulator.finish().expect("Failed to finish response");
+
+        let expected: CompletionResponse = serde_json::from_str(
+            r#"{
+                "id": "msg_014p7gG3wDgGV9EUtLvnow3U",
+                "type": "message",
+                "role": "assistant",
+                "model": "claude-3-7-sonnet-20250219",
+                "content": [
+                    {"type": "text", "text": "Okay, let's check the weather."},
+                    {
+                        "type": "tool_use",
+                        "id": "toolu_01T1x1fJ34qAmk2tNTrN7Up6",
+                        "name": "get_weather",
+                        "input": {"location": "San Francisco, CA"}
+                    }
+                ],
+                "stop_reason": "tool_use",
+                "stop_sequence": null,
+                "usage": {"input_tokens": 472, "output_tokens": 89}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(response, expected);
+    }
+
+    #[test]
+    fn test_accumulator_without_message_delta() {
+        let mut accumulator = ResponseAccumulator::new();
+        accumulator
+            .feed(serde_json::from_str(TOOL_USE_STREAM[0]).unwrap())
+            .unwrap();
+        let response = accumulator.finish().unwrap();
+        assert!(response.stop_reason.is_none());
+
+        assert!(matches!(
+            ResponseAccumulator::new().finish(),
+            Err(AnthropicError::InvalidResponse(_))
+        ));
+    }
+
+    #[test]
+    fn test_accumulator_surfaces_stream_errors() {
+        let mut accumulator = ResponseAccumulator::new();
+        let error = accumulator
+            .feed(
+                serde_json::from_str(
+                    r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+                )
+                .unwrap(),
+            )
+            .unwrap_err();
+        assert!(matches!(error, AnthropicError::Overloaded));
+    }
+
+    fn tool_use_events(fragments: &[&str]) -> Vec<StreamEvent> {
+        let mut events = vec![
+            serde_json::from_str(TOOL_USE_STREAM[0]).unwrap(),
+            serde_json::from_str(
+                r#"{"type":"content_block_start","index":0,"content_block":{"type":"tool_use","id":"toolu_01","name":"get_weather","input":{}}}"#,
+            )
+            .unwrap(),
+        ];
+        for fragment in fragments {
+            events.push(StreamEvent::ContentBlockDelta {
+                index: 0,
+                delta: ContentDelta::InputJsonDelta {
+                    partial_json: fragment.to_string(),
+                },
+            });
+        }
+        events
+    }
+
+    #[test]
+    fn test_accumulate_fragmented_input_json() {
+        let fragments = [r#"{"location": "Par"#, r#"is", "unit""#, r#": "celsius"}"#];
+        let mut accumulator = ResponseAccumulator::new();
+        for event in tool_use_events(&fragments) {
+            accumulator.feed(event).unwrap();
+        }
+
+        // No fragment is valid on its own, so nothing is parsed until the block stops
+        assert_eq!(
+            accumulator.partial_json(0),
+            Some(r#"{"location": "Paris", "unit": "celsius"}"#)
+        );
+        accumulator
+            .feed(StreamEvent::ContentBlockStop { index: 0 })
+            .unwrap();
+
+        let response = accumulator.finish().unwrap();
+        assert_eq!(
+            response.tool_uses()[0].2,
+            &serde_json::json!({"location": "Paris", "unit": "celsius"})
+        );
+    }
+
+    #[test]
+    fn test_accumulator_rejects_invalid_input_json() {
+        let mut accumulator = ResponseAccumulator::new();
+        for event in tool_use_events(&[r#"{"location": "#, r#""Paris""#]) {
+            accumulator.feed(event).unwrap();
+        }
+
+        let err = accumulator
+            .feed(StreamEvent::ContentBlockStop { index: 0 })
+            .unwrap_err();
+        assert!(matches!(err, AnthropicError::JsonError(_)));
+        assert_eq!(accumulator.partial_json(0), Some(r#"{"location": "Paris""#));
+    }
+
+    #[test]
+    fn test_deserialize_message_start_partial() {
+        let payload = r#"{
+            "id": "msg_01XFDUDYJgAACzvnptvVoYEL",
+            "type": "message",
+            "role": "assistant",
+            "content": [],
+            "model": "claude-sonnet-4-20250514",
+            "stop_reason": null,
+            "stop_sequence": null,
+            "usage": {
+                "input_tokens": 25,
+                "cache_creation_input_tokens": 0,
+                "cache_read_input_tokens": 0,
+                "output_tokens": 1,
+                "service_tier": "standard"
+            }
+        }"#;
+        let partial: PartialCompletionResponse = serde_json::from_str(payload).unwrap();
+        assert_eq!(partial.id, "msg_01XFDUDYJgAACzvnptvVoYEL");
+        assert_eq!(partial.stop_reason, None);
+        assert!(partial.content.is_empty());
+        assert_eq!(partial.usage.input_tokens, 25);
+        assert_eq!(partial.usage.output_tokens, 1);
+        assert_eq!(partial.usage.service_tier.as_deref(), Some("standard"));
+
+        let event: StreamEvent = serde_json::from_str(&format!(
+            r#"{{"type":"message_start","message":{}}}"#,
+            payload
+        ))
+        .unwrap();
+        assert_eq!(event, StreamEvent::MessageStart { message: partial });
+    }
+
+    #[test]
+    fn test_content_block_start_kind() {
+        let event: StreamEvent = serde_json::from_str(TOOL_USE_STREAM[6]).unwrap();
+        let StreamEvent::ContentBlockStart(start) = event else {
+            panic!("expected content_block_start, got {:?}", event);
+        };
+        assert_eq!(start.index, 1);
+        assert_eq!(start.kind(), ContentBlockKind::ToolUse);
+        match &start.content_block {
+            MessageContent::ToolUse { id, name, .. } => {
+                assert_eq!(id, "toolu_01T1x1fJ34qAmk2tNTrN7Up6");
+                assert_eq!(name, "get_weather");
+            }
+            other => panic!("unexpected block: {:?}", other),
+        }
+
+        let text: StreamEvent = serde_json::from_str(TOOL_USE_STREAM[1]).unwrap();
+        assert!(matches!(
+            text,
+            StreamEvent::ContentBlockStart(ref start) if start.kind() == ContentBlockKind::Text
+        ));
+    }
+
+    #[test]
+    fn test_accumulate_server_tool_usage() {
+        let mut accumulator = ResponseAccumulator::new();
+        accumulator
+            .feed(serde_json::from_str(TOOL_USE_STREAM[0]).unwrap())
+            .unwrap();
+        accumulator
+            .feed(
+                serde_json::from_str(
+                    r#"{"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":42,"server_tool_use":{"web_search_requests":2}}}"#,
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let response = accumulator.finish().unwrap();
+        assert_eq!(
+            response.usage.server_tool_use,
+            Some(ServerToolUsage {
+                web_search_requests: 2
+            })
+        );
+    }
+}
@@ -1,23 +1,37 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
 /// Tool choice configuration
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 #[serde(tag = "type")]
 pub enum ToolChoice {
     /// Model decides whether to use tools
     #[serde(rename = "auto")]
-    Auto,
+    Auto {
+        /// Whether to restrict the model to at most one tool call
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
 
     /// Force model to use a specific tool
     #[serde(rename = "tool")]
     Tool {
         /// Name of the tool to use
         name: String,
+
+        /// Whether to restrict the model to exactly one tool call
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
     },
 
     /// Force model to use any available tool
     #[serde(rename = "any")]
-    Any,
+    Any {
+        /// Whether to restrict the model to exactly one tool call
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        disable_parallel_tool_use: Option<bool>,
+    },
 
     /// Force model not to use tools
     #[serde(rename = "none")]
@@ -27,21 +41,178 @@ pub enum ToolChoice {
 impl ToolChoice {
     /// Create a new auto tool choice
     pub fn auto() -> Self {
-        Self::Auto
+        Self::Auto {
+            disable_parallel_tool_use: None,
+        }
     }
 
     /// Create a new tool-specific choice
     pub fn specific(name: impl Into<String>) -> Self {
-        Self::Tool { name: name.into() }
+        Self::Tool {
+            name: name.into(),
+            disable_parallel_tool_use: None,
+        }
     }
 
     /// Create a new any tool choice
     pub fn any() -> Self {
-        Self::Any
+        Self::Any {
+            disable_parallel_tool_use: None,
+        }
     }
 
     /// Create a new none tool choice
     pub fn none() -> Self {
         Self::None
     }
+
+    /// Set `disable_parallel_tool_use` on this choice.
+    ///
+    /// Has no effect on [`ToolChoice::None`], which does not accept the flag.
+    pub fn with_disable_parallel_tool_use(mut self, disable: bool) -> Self {
+        match &mut self {
+            Self::Auto {
+                disable_parallel_tool_use,
+            }
+            | Self::Tool {
+                disable_parallel_tool_use,
+                ..
+            }
+            | Self::Any {
+                disable_parallel_tool_use,
+            } => *disable_parallel_tool_use = Some(disable),
+            Self::None => {}
+        }
+        self
+    }
+
+    /// Whether parallel tool use is disabled by this choice
+    pub fn disable_parallel_tool_use(&self) -> Option<bool> {
+        match self {
+            Self::Auto {
+                disable_parallel_tool_use,
+            }
+            | Self::Tool {
+                disable_parallel_tool_use,
+                ..
+            }
+            | Self::Any {
+                disable_parallel_tool_use,
+            } => *disable_parallel_tool_use,
+            Self::None => None,
+        }
+    }
+}
+
+impl fmt::Display for ToolChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolChoice::Auto { .. } => f.write_str("auto"),
+            ToolChoice::Tool { name, .. } => write!(f, "tool({})", name),
+            ToolChoice::Any { .. } => f.write_str("any"),
+            ToolChoice::None => f.write_str("none"),
+        }
+    }
+}
+
+/// Parse `auto`, `any`, `none` or `tool:<name>`
+///
+/// The `tool(<name>)` form printed by `Display` is accepted as well.
+impl FromStr for ToolChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.strip_prefix("tool:").or_else(|| {
+            s.strip_prefix("tool(")
+                .and_then(|rest| rest.strip_suffix(')'))
+        });
+        match (s, name) {
+            ("auto", _) => Ok(ToolChoice::auto()),
+            ("any", _) => Ok(ToolChoice::any()),
+            ("none", _) => Ok(ToolChoice::none()),
+            (_, Some(name)) if !name.is_empty() => Ok(ToolChoice::specific(name)),
+            (other, _) => Err(format!(
+                "Unknown tool choice: {} (expected auto, any, none or tool:<name>)",
+                other
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disable_parallel_tool_use_inline() {
+        let choice = ToolChoice::auto().with_disable_parallel_tool_use(true);
+        let json = serde_json::to_value(&choice).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"type": "auto", "disable_parallel_tool_use": true})
+        );
+
+        let parsed: ToolChoice =
+            serde_json::from_str(r#"{"type":"auto","disable_parallel_tool_use":true}"#).unwrap();
+        assert_eq!(parsed, choice);
+        assert_eq!(parsed.disable_parallel_tool_use(), Some(true));
+    }
+
+    #[test]
+    fn test_tool_choice_without_flag() {
+        assert_eq!(
+            serde_json::to_value(ToolChoice::any()).unwrap(),
+            serde_json::json!({"type": "any"})
+        );
+        assert_eq!(
+            serde_json::to_value(ToolChoice::specific("get_weather")).unwrap(),
+            serde_json::json!({"type": "tool", "name": "get_weather"})
+        );
+
+        let parsed: ToolChoice = serde_json::from_str(r#"{"type":"auto"}"#).unwrap();
+        assert_eq!(parsed, ToolChoice::auto());
+        assert_eq!(
+            ToolChoice::none().with_disable_parallel_tool_use(true),
+            ToolChoice::None
+        );
+    }
+
+    #[test]
+    fn test_tool_choice_display() {
+        assert_eq!(ToolChoice::auto().to_string(), "auto");
+        assert_eq!(
+            ToolChoice::specific("get_weather").to_string(),
+            "tool(get_weather)"
+        );
+        assert_eq!(
+            ToolChoice::any()
+                .with_disable_parallel_tool_use(true)
+                .to_string(),
+            "any"
+        );
+        assert_eq!(ToolChoice::none().to_string(), "none");
+    }
+
+    #[test]
+    fn test_tool_choice_from_str() {
+        assert_eq!("auto".parse::<ToolChoice>(), Ok(ToolChoice::auto()));
+        assert_eq!("any".parse::<ToolChoice>(), Ok(ToolChoice::any()));
+        assert_eq!("none".parse::<ToolChoice>(), Ok(ToolChoice::none()));
+        assert_eq!(
+            "tool:my_tool".parse::<ToolChoice>(),
+            Ok(ToolChoice::specific("my_tool"))
+        );
+
+        let choice = ToolChoice::specific("get_weather");
+        assert_eq!(choice.to_string().parse::<ToolChoice>(), Ok(choice));
+
+        assert_eq!(
+            "sometimes".parse::<ToolChoice>(),
+            Err(
+                "Unknown tool choice: sometimes (expected auto, any, none or tool:<name>)"
+                    .to_string()
+            )
+        );
+        assert!("tool:".parse::<ToolChoice>().is_err());
+    }
 }
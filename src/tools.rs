@@ -0,0 +1,1270 @@
+use crate::messages::{json_type_name, CacheControl};
+use mcp_protocol::tool::Tool;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// A tool that can be made available to Claude
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum AnthropicTool {
+    /// Anthropic-defined tool identified by a versioned `type`
+    ///
+    /// Listed first so that its type tag is tried before falling back to a custom tool.
+    Builtin(BuiltinTool),
+
+    /// Custom tool described by a JSON schema
+    Custom(CustomTool),
+}
+
+/// A custom tool described by a JSON schema
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CustomTool {
+    /// Name of the tool
+    pub name: String,
+
+    /// Description of what the tool does
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// JSON schema for the tool input
+    pub input_schema: serde_json::Value,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+/// Anthropic-defined tools
+///
+/// Each tool carries the versioned `type` it is sent as. Deserialization only accepts
+/// a version from the variant's own family, so the variants can be told apart.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum BuiltinTool {
+    /// Server-side web search
+    WebSearch(WebSearchTool),
+
+    /// Client-side bash shell
+    Bash {
+        #[serde(rename = "type", deserialize_with = "bash_type")]
+        tool_type: BuiltinToolType,
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+
+    /// Client-side file editor
+    TextEditor {
+        #[serde(rename = "type", deserialize_with = "text_editor_type")]
+        tool_type: BuiltinToolType,
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+
+    /// Client-side computer use
+    Computer {
+        #[serde(rename = "type", deserialize_with = "computer_type")]
+        tool_type: BuiltinToolType,
+        name: String,
+        display_width_px: u32,
+        display_height_px: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        display_number: Option<u32>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+}
+
+impl BuiltinTool {
+    /// Versioned type this tool is sent as
+    pub fn tool_type(&self) -> BuiltinToolType {
+        match self {
+            BuiltinTool::WebSearch(tool) => tool.tool_type,
+            BuiltinTool::Bash { tool_type, .. }
+            | BuiltinTool::TextEditor { tool_type, .. }
+            | BuiltinTool::Computer { tool_type, .. } => *tool_type,
+        }
+    }
+
+    /// Tool family of this tool
+    pub fn family(&self) -> BuiltinToolFamily {
+        match self {
+            BuiltinTool::WebSearch(_) => BuiltinToolFamily::WebSearch,
+            BuiltinTool::Bash { .. } => BuiltinToolFamily::Bash,
+            BuiltinTool::TextEditor { .. } => BuiltinToolFamily::TextEditor,
+            BuiltinTool::Computer { .. } => BuiltinToolFamily::Computer,
+        }
+    }
+
+    /// Switch this tool to another version of the same family
+    ///
+    /// The tool is renamed to the name that version expects.
+    pub fn set_tool_type(&mut self, version: BuiltinToolType) -> Result<(), String> {
+        if version.family() != self.family() {
+            return Err(format!(
+                "{} is not a {} tool version",
+                version,
+                self.family()
+            ));
+        }
+        let (tool_type, name) = match self {
+            BuiltinTool::WebSearch(tool) => (&mut tool.tool_type, &mut tool.name),
+            BuiltinTool::Bash {
+                tool_type, name, ..
+            }
+            | BuiltinTool::TextEditor {
+                tool_type, name, ..
+            }
+            | BuiltinTool::Computer {
+                tool_type, name, ..
+            } => (tool_type, name),
+        };
+        *tool_type = version;
+        *name = version.tool_name().to_string();
+        Ok(())
+    }
+}
+
+/// A versioned Anthropic-defined tool type, such as `bash_20250124`
+///
+/// Serializes to the string returned by [`BuiltinToolType::as_str`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuiltinToolType {
+    WebSearch20250305,
+    Bash20241022,
+    Bash20250124,
+    TextEditor20241022,
+    TextEditor20250124,
+    TextEditor20250429,
+    Computer20241022,
+    Computer20250124,
+}
+
+/// A family of Anthropic-defined tools sharing a name across versions
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum BuiltinToolFamily {
+    WebSearch,
+    Bash,
+    TextEditor,
+    Computer,
+}
+
+impl BuiltinToolType {
+    /// All tool types known to this crate
+    pub const KNOWN: &'static [BuiltinToolType] = &[
+        BuiltinToolType::WebSearch20250305,
+        BuiltinToolType::Bash20241022,
+        BuiltinToolType::Bash20250124,
+        BuiltinToolType::TextEditor20241022,
+        BuiltinToolType::TextEditor20250124,
+        BuiltinToolType::TextEditor20250429,
+        BuiltinToolType::Computer20241022,
+        BuiltinToolType::Computer20250124,
+    ];
+
+    /// Get the type as sent over the wire
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuiltinToolType::WebSearch20250305 => "web_search_20250305",
+            BuiltinToolType::Bash20241022 => "bash_20241022",
+            BuiltinToolType::Bash20250124 => "bash_20250124",
+            BuiltinToolType::TextEditor20241022 => "text_editor_20241022",
+            BuiltinToolType::TextEditor20250124 => "text_editor_20250124",
+            BuiltinToolType::TextEditor20250429 => "text_editor_20250429",
+            BuiltinToolType::Computer20241022 => "computer_20241022",
+            BuiltinToolType::Computer20250124 => "computer_20250124",
+        }
+    }
+
+    /// Tool family this version belongs to
+    pub fn family(&self) -> BuiltinToolFamily {
+        match self {
+            BuiltinToolType::WebSearch20250305 => BuiltinToolFamily::WebSearch,
+            BuiltinToolType::Bash20241022 | BuiltinToolType::Bash20250124 => {
+                BuiltinToolFamily::Bash
+            }
+            BuiltinToolType::TextEditor20241022
+            | BuiltinToolType::TextEditor20250124
+            | BuiltinToolType::TextEditor20250429 => BuiltinToolFamily::TextEditor,
+            BuiltinToolType::Computer20241022 | BuiltinToolType::Computer20250124 => {
+                BuiltinToolFamily::Computer
+            }
+        }
+    }
+
+    /// Name the API requires for a tool of this version
+    pub fn tool_name(&self) -> &'static str {
+        match self {
+            BuiltinToolType::WebSearch20250305 => "web_search",
+            BuiltinToolType::Bash20241022 | BuiltinToolType::Bash20250124 => "bash",
+            BuiltinToolType::TextEditor20241022 | BuiltinToolType::TextEditor20250124 => {
+                "str_replace_editor"
+            }
+            BuiltinToolType::TextEditor20250429 => "str_replace_based_edit_tool",
+            BuiltinToolType::Computer20241022 | BuiltinToolType::Computer20250124 => "computer",
+        }
+    }
+
+    /// Beta header this version must be sent with, if any
+    pub fn required_beta(&self) -> Option<&'static str> {
+        match self {
+            BuiltinToolType::Bash20241022
+            | BuiltinToolType::TextEditor20241022
+            | BuiltinToolType::Computer20241022 => Some("computer-use-2024-10-22"),
+            BuiltinToolType::Computer20250124 => Some("computer-use-2025-01-24"),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BuiltinToolType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for BuiltinToolType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::KNOWN
+            .iter()
+            .find(|tool_type| tool_type.as_str() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown built-in tool type: {}", s))
+    }
+}
+
+impl Serialize for BuiltinToolType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for BuiltinToolType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Deserialize a tool type, rejecting versions from other tool families
+fn family_type<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    family: BuiltinToolFamily,
+) -> Result<BuiltinToolType, D::Error> {
+    let tool_type = BuiltinToolType::deserialize(deserializer)?;
+    if tool_type.family() != family {
+        return Err(de::Error::custom(format!(
+            "{} is not a {} tool version",
+            tool_type, family
+        )));
+    }
+    Ok(tool_type)
+}
+
+fn web_search_type<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BuiltinToolType, D::Error> {
+    family_type(deserializer, BuiltinToolFamily::WebSearch)
+}
+
+fn bash_type<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BuiltinToolType, D::Error> {
+    family_type(deserializer, BuiltinToolFamily::Bash)
+}
+
+fn text_editor_type<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BuiltinToolType, D::Error> {
+    family_type(deserializer, BuiltinToolFamily::TextEditor)
+}
+
+fn computer_type<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BuiltinToolType, D::Error> {
+    family_type(deserializer, BuiltinToolFamily::Computer)
+}
+
+impl BuiltinToolFamily {
+    /// Newest version of this tool known to this crate
+    ///
+    /// The `AnthropicTool` constructors use this version.
+    pub fn latest(&self) -> BuiltinToolType {
+        match self {
+            BuiltinToolFamily::WebSearch => BuiltinToolType::WebSearch20250305,
+            BuiltinToolFamily::Bash => BuiltinToolType::Bash20250124,
+            BuiltinToolFamily::TextEditor => BuiltinToolType::TextEditor20250429,
+            BuiltinToolFamily::Computer => BuiltinToolType::Computer20250124,
+        }
+    }
+}
+
+impl fmt::Display for BuiltinToolFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BuiltinToolFamily::WebSearch => "web_search",
+            BuiltinToolFamily::Bash => "bash",
+            BuiltinToolFamily::TextEditor => "text_editor",
+            BuiltinToolFamily::Computer => "computer",
+        })
+    }
+}
+
+/// Configuration for the web search server tool
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WebSearchTool {
+    /// Versioned tool type, a web search version
+    #[serde(rename = "type", deserialize_with = "web_search_type")]
+    pub tool_type: BuiltinToolType,
+
+    /// Name of the tool, always "web_search"
+    pub name: String,
+
+    /// Maximum number of searches per request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
+
+    /// Only include results from these domains
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_domains: Option<Vec<String>>,
+
+    /// Never include results from these domains
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked_domains: Option<Vec<String>>,
+
+    /// Approximate location used to localize results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_location: Option<UserLocation>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_control: Option<CacheControl>,
+}
+
+impl Default for WebSearchTool {
+    fn default() -> Self {
+        let tool_type = BuiltinToolFamily::WebSearch.latest();
+        Self {
+            tool_type,
+            name: tool_type.tool_name().to_string(),
+            max_uses: None,
+            allowed_domains: None,
+            blocked_domains: None,
+            user_location: None,
+            cache_control: None,
+        }
+    }
+}
+
+/// Approximate user location for web search
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct UserLocation {
+    /// Location type, always "approximate"
+    #[serde(rename = "type")]
+    pub location_type: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+}
+
+/// A remote MCP server that Claude can call tools on directly
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct McpServerConfig {
+    /// Connection type, always "url"
+    #[serde(rename = "type")]
+    pub server_type: String,
+
+    /// URL of the MCP server
+    pub url: String,
+
+    /// Name used to identify the server in tool use blocks
+    pub name: String,
+
+    /// OAuth bearer token for the server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorization_token: Option<String>,
+
+    /// Which of the server's tools are available
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_configuration: Option<McpToolConfiguration>,
+}
+
+/// Tool configuration for an MCP server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct McpToolConfiguration {
+    /// Whether the server's tools are enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+
+    /// Restrict the server to these tools
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_tools: Option<Vec<String>>,
+}
+
+impl McpServerConfig {
+    /// Create a new URL-based MCP server configuration
+    pub fn url(name: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            server_type: "url".to_string(),
+            url: url.into(),
+            name: name.into(),
+            authorization_token: None,
+            tool_configuration: None,
+        }
+    }
+}
+
+/// Typed JSON schema for a custom tool's input
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ToolParameters {
+    /// Schema type, always "object"
+    #[serde(rename = "type")]
+    pub param_type: String,
+
+    /// Input properties by name, kept sorted so serialization is deterministic
+    pub properties: BTreeMap<String, ParameterProperty>,
+
+    /// Names of required properties
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+}
+
+/// Schema for a single tool input property
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParameterProperty {
+    /// JSON schema type ("string", "number", "integer", "boolean", "array", "object")
+    #[serde(rename = "type")]
+    pub property_type: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Allowed values
+    #[serde(rename = "enum", skip_serializing_if = "Option::is_none")]
+    pub enum_values: Option<Vec<String>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<f64>,
+
+    /// Schema of the elements of an array
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Box<ParameterProperty>>,
+
+    /// Properties of a nested object
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub properties: Option<BTreeMap<String, ParameterProperty>>,
+
+    /// Names of required properties of a nested object
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required: Option<Vec<String>>,
+}
+
+impl ToolParameters {
+    /// Create an empty object schema
+    pub fn new() -> Self {
+        Self {
+            param_type: "object".to_string(),
+            properties: BTreeMap::new(),
+            required: Vec::new(),
+        }
+    }
+
+    /// Add an optional property
+    pub fn property(mut self, name: impl Into<String>, property: ParameterProperty) -> Self {
+        self.properties.insert(name.into(), property);
+        self
+    }
+
+    /// Add a required property
+    pub fn required_property(
+        mut self,
+        name: impl Into<String>,
+        property: ParameterProperty,
+    ) -> Self {
+        let name = name.into();
+        self.required.push(name.clone());
+        self.properties.insert(name, property);
+        self
+    }
+
+    /// Check a tool use input against this schema
+    ///
+    /// Verifies that required properties are present and that property values have the
+    /// declared JSON type, descending into nested objects and array elements. Other
+    /// constraints such as `enum` and `minimum` are not checked. Every problem found is
+    /// returned, with nested fields named by path, e.g. `events[0].title`.
+    pub fn validate(&self, input: &serde_json::Value) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        match input.as_object() {
+            Some(object) => {
+                validate_object(&self.properties, &self.required, object, "", &mut errors)
+            }
+            None => errors.push(format!(
+                "input should be object, got {}",
+                schema_type_name(input)
+            )),
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn validate_object(
+    properties: &BTreeMap<String, ParameterProperty>,
+    required: &[String],
+    object: &serde_json::Map<String, serde_json::Value>,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    let field_path = |name: &str| {
+        if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", path, name)
+        }
+    };
+    for name in required {
+        if !object.contains_key(name) {
+            errors.push(format!("missing required field {}", field_path(name)));
+        }
+    }
+    for (name, property) in properties {
+        if let Some(value) = object.get(name) {
+            property.validate_value(value, &field_path(name), errors);
+        }
+    }
+}
+
+/// JSON schema type name of a value, reporting whole numbers as "integer"
+fn schema_type_name(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Number(number) if !number.is_f64() => "integer",
+        other => json_type_name(other),
+    }
+}
+
+impl Default for ToolParameters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<ToolParameters> for serde_json::Value {
+    fn from(parameters: ToolParameters) -> Self {
+        serde_json::to_value(parameters).expect("tool parameters are always valid JSON")
+    }
+}
+
+impl ParameterProperty {
+    fn of_type(property_type: &str) -> Self {
+        Self {
+            property_type: property_type.to_string(),
+            description: None,
+            enum_values: None,
+            minimum: None,
+            maximum: None,
+            items: None,
+            properties: None,
+            required: None,
+        }
+    }
+
+    /// Create a string property
+    pub fn string() -> Self {
+        Self::of_type("string")
+    }
+
+    /// Create a number property
+    pub fn number() -> Self {
+        Self::of_type("number")
+    }
+
+    /// Create an integer property
+    pub fn integer() -> Self {
+        Self::of_type("integer")
+    }
+
+    /// Create a boolean property
+    pub fn boolean() -> Self {
+        Self::of_type("boolean")
+    }
+
+    /// Create an array property whose elements match `items`
+    pub fn array(items: ParameterProperty) -> Self {
+        Self {
+            items: Some(Box::new(items)),
+            ..Self::of_type("array")
+        }
+    }
+
+    /// Create an empty nested object property
+    pub fn object() -> Self {
+        Self {
+            properties: Some(BTreeMap::new()),
+            ..Self::of_type("object")
+        }
+    }
+
+    /// Set the description
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Restrict the property to a set of allowed values
+    pub fn enum_values<S: Into<String>>(mut self, values: impl IntoIterator<Item = S>) -> Self {
+        self.enum_values = Some(values.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the minimum allowed value
+    pub fn minimum(mut self, minimum: f64) -> Self {
+        self.minimum = Some(minimum);
+        self
+    }
+
+    /// Set the maximum allowed value
+    pub fn maximum(mut self, maximum: f64) -> Self {
+        self.maximum = Some(maximum);
+        self
+    }
+
+    fn validate_value(&self, value: &serde_json::Value, path: &str, errors: &mut Vec<String>) {
+        let matches = match self.property_type.as_str() {
+            "string" => value.is_string(),
+            "number" => value.is_number(),
+            "integer" => {
+                value.is_i64()
+                    || value.is_u64()
+                    || value.as_f64().is_some_and(|number| number.fract() == 0.0)
+            }
+            "boolean" => value.is_boolean(),
+            "array" => value.is_array(),
+            "object" => value.is_object(),
+            // Types this crate doesn't model are not checked
+            _ => true,
+        };
+        if !matches {
+            errors.push(format!(
+                "field {} should be {}, got {}",
+                path,
+                self.property_type,
+                schema_type_name(value)
+            ));
+            return;
+        }
+        if let (Some(items), Some(elements)) = (&self.items, value.as_array()) {
+            for (index, element) in elements.iter().enumerate() {
+                items.validate_value(element, &format!("{}[{}]", path, index), errors);
+            }
+        }
+        if let (Some(properties), Some(object)) = (&self.properties, value.as_object()) {
+            let required = self.required.as_deref().unwrap_or_default();
+            validate_object(properties, required, object, path, errors);
+        }
+    }
+
+    /// Add an optional property to a nested object
+    pub fn property(mut self, name: impl Into<String>, property: ParameterProperty) -> Self {
+        self.properties
+            .get_or_insert_with(BTreeMap::new)
+            .insert(name.into(), property);
+        self
+    }
+
+    /// Add a required property to a nested object
+    pub fn required_property(
+        mut self,
+        name: impl Into<String>,
+        property: ParameterProperty,
+    ) -> Self {
+        let name = name.into();
+        self.required
+            .get_or_insert_with(Vec::new)
+            .push(name.clone());
+        self.property(name, property)
+    }
+}
+
+impl AnthropicTool {
+    /// Create a new custom tool
+    pub fn custom(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        input_schema: serde_json::Value,
+    ) -> Self {
+        Self::Custom(CustomTool {
+            name: name.into(),
+            description: Some(description.into()),
+            input_schema,
+            cache_control: None,
+        })
+    }
+
+    /// Create a new web search tool with default settings
+    pub fn web_search() -> Self {
+        WebSearchTool::default().into()
+    }
+
+    /// Create the latest version of the built-in bash tool
+    pub fn bash() -> Self {
+        let tool_type = BuiltinToolFamily::Bash.latest();
+        Self::Builtin(BuiltinTool::Bash {
+            tool_type,
+            name: tool_type.tool_name().to_string(),
+            cache_control: None,
+        })
+    }
+
+    /// Create the latest version of the built-in text editor tool
+    pub fn text_editor() -> Self {
+        let tool_type = BuiltinToolFamily::TextEditor.latest();
+        Self::Builtin(BuiltinTool::TextEditor {
+            tool_type,
+            name: tool_type.tool_name().to_string(),
+            cache_control: None,
+        })
+    }
+
+    /// Create the latest version of the built-in computer use tool for a display
+    /// of the given size
+    pub fn computer(width: u32, height: u32, display_number: Option<u32>) -> Self {
+        let tool_type = BuiltinToolFamily::Computer.latest();
+        Self::Builtin(BuiltinTool::Computer {
+            tool_type,
+            name: tool_type.tool_name().to_string(),
+            display_width_px: width,
+            display_height_px: height,
+            display_number,
+            cache_control: None,
+        })
+    }
+
+    /// Use a specific version of a built-in tool instead of the latest
+    ///
+    /// Fails for custom tools and for versions of a different tool family.
+    pub fn with_version(mut self, version: BuiltinToolType) -> Result<Self, String> {
+        match &mut self {
+            AnthropicTool::Builtin(tool) => tool.set_tool_type(version)?,
+            AnthropicTool::Custom(tool) => {
+                return Err(format!("custom tool {} has no versions", tool.name))
+            }
+        }
+        Ok(self)
+    }
+
+    /// Get the name Claude uses to refer to this tool
+    pub fn name(&self) -> &str {
+        match self {
+            AnthropicTool::Custom(tool) => &tool.name,
+            AnthropicTool::Builtin(BuiltinTool::WebSearch(tool)) => &tool.name,
+            AnthropicTool::Builtin(BuiltinTool::Bash { name, .. })
+            | AnthropicTool::Builtin(BuiltinTool::TextEditor { name, .. })
+            | AnthropicTool::Builtin(BuiltinTool::Computer { name, .. }) => name,
+        }
+    }
+
+    /// Get the cache breakpoint on this tool definition, if any
+    pub fn cache_control(&self) -> Option<&CacheControl> {
+        match self {
+            AnthropicTool::Custom(tool) => tool.cache_control.as_ref(),
+            AnthropicTool::Builtin(BuiltinTool::WebSearch(tool)) => tool.cache_control.as_ref(),
+            AnthropicTool::Builtin(BuiltinTool::Bash { cache_control, .. })
+            | AnthropicTool::Builtin(BuiltinTool::TextEditor { cache_control, .. })
+            | AnthropicTool::Builtin(BuiltinTool::Computer { cache_control, .. }) => {
+                cache_control.as_ref()
+            }
+        }
+    }
+}
+
+impl From<CustomTool> for AnthropicTool {
+    fn from(tool: CustomTool) -> Self {
+        Self::Custom(tool)
+    }
+}
+
+impl From<WebSearchTool> for AnthropicTool {
+    fn from(tool: WebSearchTool) -> Self {
+        Self::Builtin(BuiltinTool::WebSearch(tool))
+    }
+}
+
+/// Convert an MCP tool into a custom tool. MCP annotations have no Anthropic
+/// equivalent and are dropped.
+impl From<Tool> for AnthropicTool {
+    fn from(tool: Tool) -> Self {
+        Self::Custom(CustomTool {
+            name: tool.name,
+            description: tool.description,
+            input_schema: tool.input_schema,
+            cache_control: None,
+        })
+    }
+}
+
+/// Convert a custom tool back into an MCP tool. Built-in tools are defined by
+/// Anthropic and have no MCP equivalent.
+impl TryFrom<AnthropicTool> for Tool {
+    type Error = String;
+
+    fn try_from(tool: AnthropicTool) -> Result<Self, Self::Error> {
+        match tool {
+            AnthropicTool::Custom(tool) => Ok(Tool {
+                name: tool.name,
+                description: tool.description,
+                input_schema: tool.input_schema,
+                annotations: None,
+            }),
+            AnthropicTool::Builtin(_) => Err(format!(
+                "built-in tool {} has no MCP equivalent",
+                tool.name()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_web_search_tool() {
+        let tool: AnthropicTool = WebSearchTool {
+            max_uses: Some(5),
+            allowed_domains: Some(vec!["example.com".to_string(), "docs.rs".to_string()]),
+            ..WebSearchTool::default()
+        }
+        .into();
+
+        assert_eq!(
+            serde_json::to_value(&tool).unwrap(),
+            serde_json::json!({
+                "type": "web_search_20250305",
+                "name": "web_search",
+                "max_uses": 5,
+                "allowed_domains": ["example.com", "docs.rs"]
+            })
+        );
+        assert_eq!(tool.name(), "web_search");
+
+        assert_eq!(
+            serde_json::to_value(AnthropicTool::web_search()).unwrap(),
+            serde_json::json!({"type": "web_search_20250305", "name": "web_search"})
+        );
+    }
+
+    #[test]
+    fn test_deserialize_mixed_tools() {
+        let json = r#"[
+            {
+                "name": "get_weather",
+                "description": "Get the current weather in a given location",
+                "input_schema": {
+                    "type": "object",
+                    "properties": {"location": {"type": "string"}},
+                    "required": ["location"]
+                }
+            },
+            {"type": "web_search_20250305", "name": "web_search", "max_uses": 3}
+        ]"#;
+
+        let tools: Vec<AnthropicTool> = serde_json::from_str(json).unwrap();
+        assert!(matches!(tools[0], AnthropicTool::Custom(_)));
+        match &tools[1] {
+            AnthropicTool::Builtin(BuiltinTool::WebSearch(tool)) => {
+                assert_eq!(tool.max_uses, Some(3))
+            }
+            other => panic!("Unexpected tool: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_round_trip_array_of_objects_parameter() {
+        let parameters = ToolParameters::new().required_property(
+            "events",
+            ParameterProperty::array(
+                ParameterProperty::object()
+                    .required_property("title", ParameterProperty::string())
+                    .property(
+                        "priority",
+                        ParameterProperty::string().enum_values(["low", "high"]),
+                    ),
+            )
+            .description("Events to schedule"),
+        );
+        let tool = AnthropicTool::custom("schedule", "Schedule events", parameters.into());
+
+        let json = serde_json::to_value(&tool).unwrap();
+        assert_eq!(
+            json["input_schema"],
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "events": {
+                        "type": "array",
+                        "description": "Events to schedule",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "title": {"type": "string"},
+                                "priority": {"type": "string", "enum": ["low", "high"]}
+                            },
+                            "required": ["title"]
+                        }
+                    }
+                },
+                "required": ["events"]
+            })
+        );
+
+        let parsed: ToolParameters = serde_json::from_value(json["input_schema"].clone()).unwrap();
+        let items = parsed.properties["events"].items.as_ref().unwrap();
+        assert_eq!(items.property_type, "object");
+        assert_eq!(items.required, Some(vec!["title".to_string()]));
+        assert_eq!(
+            items.properties.as_ref().unwrap()["priority"].enum_values,
+            Some(vec!["low".to_string(), "high".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_tool_schema_serialization_is_stable() {
+        let build = || {
+            ToolParameters::new()
+                .required_property("query", ParameterProperty::string())
+                .property("limit", ParameterProperty::integer().minimum(1.0))
+                .property("include_archived", ParameterProperty::boolean())
+                .property(
+                    "sort",
+                    ParameterProperty::string().enum_values(["asc", "desc"]),
+                )
+                .property(
+                    "tags",
+                    ParameterProperty::array(ParameterProperty::string()),
+                )
+        };
+
+        let first = serde_json::to_string(&AnthropicTool::custom(
+            "search",
+            "Search documents",
+            build().into(),
+        ))
+        .unwrap();
+        let second = serde_json::to_string(&AnthropicTool::custom(
+            "search",
+            "Search documents",
+            build().into(),
+        ))
+        .unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_builtin_tool_type_serialization() {
+        for tool_type in BuiltinToolType::KNOWN {
+            assert_eq!(
+                serde_json::to_value(tool_type).unwrap(),
+                serde_json::json!(tool_type.as_str())
+            );
+            let parsed: BuiltinToolType =
+                serde_json::from_value(serde_json::json!(tool_type.as_str())).unwrap();
+            assert_eq!(parsed, *tool_type);
+            assert!(tool_type.as_str().starts_with(
+                serde_json::to_value(tool_type.family())
+                    .unwrap()
+                    .as_str()
+                    .unwrap()
+            ));
+        }
+        assert_eq!(
+            serde_json::to_string(&BuiltinToolType::Bash20241022).unwrap(),
+            r#""bash_20241022""#
+        );
+        assert_eq!(
+            BuiltinToolType::TextEditor20250124.to_string(),
+            "text_editor_20250124"
+        );
+    }
+
+    #[test]
+    fn test_builtin_tool_type_latest() {
+        assert_eq!(
+            BuiltinToolFamily::Bash.latest(),
+            BuiltinToolType::Bash20250124
+        );
+        assert_eq!(
+            BuiltinToolFamily::Computer.latest().as_str(),
+            "computer_20250124"
+        );
+
+        // The constructors use the latest version of each family
+        let tools = [
+            AnthropicTool::web_search(),
+            AnthropicTool::bash(),
+            AnthropicTool::text_editor(),
+            AnthropicTool::computer(1024, 768, None),
+        ];
+        for tool in tools {
+            let AnthropicTool::Builtin(builtin) = &tool else {
+                panic!("expected a built-in tool");
+            };
+            let tool_type = builtin.tool_type();
+            assert_eq!(tool_type.family().latest(), tool_type);
+            assert_eq!(
+                serde_json::to_value(&tool).unwrap()["type"],
+                tool_type.as_str()
+            );
+        }
+    }
+
+    #[test]
+    fn test_builtin_tool_versions() {
+        let editor = AnthropicTool::text_editor()
+            .with_version(BuiltinToolType::TextEditor20250124)
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&editor).unwrap(),
+            serde_json::json!({"type": "text_editor_20250124", "name": "str_replace_editor"})
+        );
+
+        let computer = AnthropicTool::computer(1024, 768, None)
+            .with_version(BuiltinToolType::Computer20241022)
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&computer).unwrap()["type"],
+            "computer_20241022"
+        );
+
+        assert_eq!(
+            AnthropicTool::bash()
+                .with_version(BuiltinToolType::Computer20241022)
+                .unwrap_err(),
+            "computer_20241022 is not a bash tool version"
+        );
+        assert!(
+            AnthropicTool::custom("search", "Search", serde_json::json!({}))
+                .with_version(BuiltinToolType::Bash20241022)
+                .is_err()
+        );
+
+        // Tools with the same shape are told apart by the family of their type
+        let tools: Vec<AnthropicTool> = serde_json::from_str(
+            r#"[
+                {"type": "bash_20241022", "name": "bash"},
+                {"type": "text_editor_20241022", "name": "str_replace_editor"}
+            ]"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            &tools[0],
+            AnthropicTool::Builtin(BuiltinTool::Bash {
+                tool_type: BuiltinToolType::Bash20241022,
+                ..
+            })
+        ));
+        assert!(matches!(
+            &tools[1],
+            AnthropicTool::Builtin(BuiltinTool::TextEditor {
+                tool_type: BuiltinToolType::TextEditor20241022,
+                ..
+            })
+        ));
+
+        assert!(serde_json::from_str::<BuiltinTool>(
+            r#"{"type": "bash_20991231", "name": "bash"}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_validate_tool_input() {
+        let parameters = ToolParameters::new()
+            .required_property("query", ParameterProperty::string())
+            .property("limit", ParameterProperty::integer())
+            .property("include_archived", ParameterProperty::boolean())
+            .property(
+                "events",
+                ParameterProperty::array(
+                    ParameterProperty::object()
+                        .required_property("title", ParameterProperty::string()),
+                ),
+            );
+
+        let valid = serde_json::json!({
+            "query": "rust",
+            "limit": 10,
+            "events": [{"title": "Launch"}]
+        });
+        assert_eq!(parameters.validate(&valid), Ok(()));
+
+        let missing = serde_json::json!({"limit": 10});
+        assert_eq!(
+            parameters.validate(&missing),
+            Err(vec!["missing required field query".to_string()])
+        );
+
+        let mismatched = serde_json::json!({
+            "query": "rust",
+            "limit": "ten",
+            "include_archived": true,
+            "events": [{"title": 1}, {}]
+        });
+        assert_eq!(
+            parameters.validate(&mismatched),
+            Err(vec![
+                "field events[0].title should be string, got integer".to_string(),
+                "missing required field events[1].title".to_string(),
+                "field limit should be integer, got string".to_string(),
+            ])
+        );
+
+        assert_eq!(
+            parameters.validate(&serde_json::json!("rust")),
+            Err(vec!["input should be object, got string".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_serialize_mcp_server_config() {
+        let server = McpServerConfig {
+            authorization_token: Some("YOUR_TOKEN".to_string()),
+            tool_configuration: Some(McpToolConfiguration {
+                enabled: Some(true),
+                allowed_tools: Some(vec![
+                    "example_tool_1".to_string(),
+                    "example_tool_2".to_string(),
+                ]),
+            }),
+            ..McpServerConfig::url(
+                "example-mcp",
+                "https://example-server.modelcontextprotocol.io/sse",
+            )
+        };
+
+        assert_eq!(
+            serde_json::to_value(&server).unwrap(),
+            serde_json::json!({
+                "type": "url",
+                "url": "https://example-server.modelcontextprotocol.io/sse",
+                "name": "example-mcp",
+                "authorization_token": "YOUR_TOKEN",
+                "tool_configuration": {
+                    "enabled": true,
+                    "allowed_tools": ["example_tool_1", "example_tool_2"]
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_serialize_bash_and_text_editor_tools() {
+        assert_eq!(
+            serde_json::to_string(&AnthropicTool::bash()).unwrap(),
+            r#"{"type":"bash_20250124","name":"bash"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&AnthropicTool::text_editor()).unwrap(),
+            r#"{"type":"text_editor_20250429","name":"str_replace_based_edit_tool"}"#
+        );
+
+        let request = crate::messages::CompletionRequest::builder()
+            .model("claude-sonnet-4-20250514")
+            .max_tokens(1024)
+            .message(crate::messages::Message::user("List the files"))
+            .tools(vec![AnthropicTool::bash(), AnthropicTool::text_editor()])
+            .build()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["tools"],
+            serde_json::json!([
+                {"type": "bash_20250124", "name": "bash"},
+                {"type": "text_editor_20250429", "name": "str_replace_based_edit_tool"}
+            ])
+        );
+    }
+
+    #[test]
+    fn test_serialize_computer_tool() {
+        assert_eq!(
+            serde_json::to_value(AnthropicTool::computer(1024, 768, Some(1))).unwrap(),
+            serde_json::json!({
+                "type": "computer_20250124",
+                "name": "computer",
+                "display_width_px": 1024,
+                "display_height_px": 768,
+                "display_number": 1
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(AnthropicTool::computer(1280, 800, None)).unwrap(),
+            serde_json::json!({
+                "type": "computer_20250124",
+                "name": "computer",
+                "display_width_px": 1280,
+                "display_height_px": 800
+            })
+        );
+
+        let tools = vec![
+            AnthropicTool::computer(1024, 768, None),
+            AnthropicTool::custom(
+                "get_weather",
+                "Get the weather",
+                serde_json::json!({"type": "object", "properties": {}}),
+            ),
+        ];
+        let json = serde_json::to_string(&tools).unwrap();
+        let parsed: Vec<AnthropicTool> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, tools);
+    }
+
+    #[test]
+    fn test_mcp_tool_round_trip() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"path": {"type": "string"}},
+            "required": ["path"]
+        });
+        let mcp_tool = Tool {
+            name: "read_file".to_string(),
+            description: Some("Read a file".to_string()),
+            input_schema: schema.clone(),
+            annotations: None,
+        };
+
+        let tool = AnthropicTool::from(mcp_tool);
+        assert_eq!(
+            tool,
+            AnthropicTool::custom("read_file", "Read a file", schema.clone())
+        );
+
+        let back = Tool::try_from(tool).unwrap();
+        assert_eq!(back.name, "read_file");
+        assert_eq!(back.description.as_deref(), Some("Read a file"));
+        assert_eq!(back.input_schema, schema);
+    }
+
+    #[test]
+    fn test_builtin_tool_to_mcp_fails() {
+        let err = Tool::try_from(AnthropicTool::bash()).unwrap_err();
+        assert_eq!(err, "built-in tool bash has no MCP equivalent");
+        assert!(Tool::try_from(AnthropicTool::web_search()).is_err());
+    }
+}
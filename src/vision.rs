@@ -0,0 +1,77 @@
+use crate::messages::MessageContent;
+
+/// Images whose long edge exceeds this many pixels are scaled down by the API
+pub const MAX_LONG_EDGE_PX: u32 = 1568;
+
+/// Approximate token cost of the largest image the API processes without resizing
+pub const MAX_IMAGE_TOKENS: u32 = 1600;
+
+/// Estimate the input tokens for an image of the given dimensions
+///
+/// Uses the documented `(width * height) / 750` formula, rounded up. Images
+/// are first scaled down to the same size the API would resize them to, so the
+/// estimate never exceeds [`MAX_IMAGE_TOKENS`].
+pub fn estimate_image_tokens(width: u32, height: u32) -> u32 {
+    let (mut width, mut height) = (u64::from(width), u64::from(height));
+    let long_edge = width.max(height);
+    if long_edge > u64::from(MAX_LONG_EDGE_PX) {
+        width = width * u64::from(MAX_LONG_EDGE_PX) / long_edge;
+        height = height * u64::from(MAX_LONG_EDGE_PX) / long_edge;
+    }
+    let tokens = (width * height).div_ceil(750);
+    tokens.min(u64::from(MAX_IMAGE_TOKENS)) as u32
+}
+
+/// Estimate the input tokens for an image content block
+///
+/// The API doesn't report the dimensions of base64 or URL images, so pass the
+/// decoded `(width, height)` when known. Without dimensions the estimate is
+/// [`MAX_IMAGE_TOKENS`]. Returns `None` for blocks that are not images.
+pub fn estimate_block_tokens(
+    block: &MessageContent,
+    dimensions: Option<(u32, u32)>,
+) -> Option<u32> {
+    match block {
+        MessageContent::Image { .. } => Some(match dimensions {
+            Some((width, height)) => estimate_image_tokens(width, height),
+            None => MAX_IMAGE_TOKENS,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::ImageSource;
+
+    #[test]
+    fn test_estimate_image_tokens() {
+        // 1000 * 1000 / 750 = 1333.3
+        assert_eq!(estimate_image_tokens(1000, 1000), 1334);
+        assert_eq!(estimate_image_tokens(200, 200), 54);
+
+        // Scaled down to 1568x1176 before counting, then capped
+        assert_eq!(estimate_image_tokens(4000, 3000), MAX_IMAGE_TOKENS);
+        assert_eq!(estimate_image_tokens(0, 0), 0);
+    }
+
+    #[test]
+    fn test_estimate_block_tokens() {
+        let image = MessageContent::Image {
+            source: ImageSource::Url {
+                url: "https://example.com/cat.png".to_string(),
+            },
+            cache_control: None,
+        };
+        assert_eq!(
+            estimate_block_tokens(&image, Some((1000, 1000))),
+            Some(1334)
+        );
+        assert_eq!(estimate_block_tokens(&image, None), Some(MAX_IMAGE_TOKENS));
+        assert_eq!(
+            estimate_block_tokens(&MessageContent::text("hi"), Some((1000, 1000))),
+            None
+        );
+    }
+}